@@ -1,152 +1,325 @@
+use crate::storage;
 use crate::GameState;
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 pub struct ActionsPlugin;
 
-// This plugin listens for keyboard input and converts the input into Actions
+// This plugin listens for keyboard and gamepad input and converts the input into Actions
 // Actions can then be used as a resource in other systems to act on the player input.
 impl Plugin for ActionsPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<Actions>().add_system_set(
-            SystemSet::on_update(GameState::Playing).with_system(set_movement_actions),
-        );
+        app.init_resource::<Actions>()
+            .init_resource::<Keymap>()
+            .add_system(log_gamepad_connections)
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(set_movement_actions.label(ActionsLabel))
+                    .with_system(
+                        set_gamepad_actions
+                            .label(ActionsLabel)
+                            .after(set_movement_actions),
+                    ),
+            );
     }
 }
 
+/// Systems filling [Actions] from the keyboard and gamepads.
+/// Other input sources run after them and only move the player when they did not.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, SystemLabel)]
+pub struct ActionsLabel;
+
+/// Stick positions closer to the center than this are ignored
+const STICK_DEADZONE: f32 = 0.2;
+
 #[derive(Default)]
 pub struct Actions {
+    /// Length up to 1, below 1 when an analog stick is only partly tilted
     pub player_movement: Option<Vec2>,
+    /// Stays set until the next gameplay tick consumes it, so a press is neither lost
+    /// nor applied twice when frames and ticks don't line up
     pub player_jump: Option<bool>,
+    /// The jump button is held down, releasing it early makes a shorter jump
+    pub player_jump_held: bool,
+    /// Latched like `player_jump`
+    pub build_tower: Option<bool>,
+    /// Latched like `player_jump`
+    pub player_attack: Option<bool>,
 }
 
-fn set_movement_actions(mut actions: ResMut<Actions>, keyboard_input: Res<Input<KeyCode>>) {
-    if GameControl::Up.just_pressed(&keyboard_input) {
+fn set_movement_actions(
+    mut actions: ResMut<Actions>,
+    keymap: Res<Keymap>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut keyboard_movement: Local<Vec2>,
+) {
+    if GameControl::Jump.just_pressed(&keymap, &keyboard_input) {
         actions.player_jump = Some(true);
-    } else {
-        actions.player_jump = None;
+    }
+    actions.player_jump_held = GameControl::Jump.pressed(&keymap, &keyboard_input);
+
+    if GameControl::Build.just_pressed(&keymap, &keyboard_input) {
+        actions.build_tower = Some(true);
+    }
+
+    if GameControl::Attack.just_pressed(&keymap, &keyboard_input) {
+        actions.player_attack = Some(true);
     }
 
-    if GameControl::Up.just_released(&keyboard_input)
-        || GameControl::Up.pressed(&keyboard_input)
-        || GameControl::Left.just_released(&keyboard_input)
-        || GameControl::Left.pressed(&keyboard_input)
-        || GameControl::Down.just_released(&keyboard_input)
-        || GameControl::Down.pressed(&keyboard_input)
-        || GameControl::Right.just_released(&keyboard_input)
-        || GameControl::Right.pressed(&keyboard_input)
+    if GameControl::Up.just_released(&keymap, &keyboard_input)
+        || GameControl::Up.pressed(&keymap, &keyboard_input)
+        || GameControl::Left.just_released(&keymap, &keyboard_input)
+        || GameControl::Left.pressed(&keymap, &keyboard_input)
+        || GameControl::Down.just_released(&keymap, &keyboard_input)
+        || GameControl::Down.pressed(&keymap, &keyboard_input)
+        || GameControl::Right.just_released(&keymap, &keyboard_input)
+        || GameControl::Right.pressed(&keymap, &keyboard_input)
     {
         let mut player_movement = Vec2::ZERO;
 
-        if GameControl::Up.just_released(&keyboard_input)
-            || GameControl::Down.just_released(&keyboard_input)
+        if GameControl::Up.just_released(&keymap, &keyboard_input)
+            || GameControl::Down.just_released(&keymap, &keyboard_input)
         {
-            if GameControl::Up.pressed(&keyboard_input) {
+            if GameControl::Up.pressed(&keymap, &keyboard_input) {
                 player_movement.y = 1.;
-            } else if GameControl::Down.pressed(&keyboard_input) {
+            } else if GameControl::Down.pressed(&keymap, &keyboard_input) {
                 player_movement.y = -1.;
             } else {
                 player_movement.y = 0.;
             }
-        } else if GameControl::Up.just_pressed(&keyboard_input) {
+        } else if GameControl::Up.just_pressed(&keymap, &keyboard_input) {
             player_movement.y = 1.;
-        } else if GameControl::Down.just_pressed(&keyboard_input) {
+        } else if GameControl::Down.just_pressed(&keymap, &keyboard_input) {
             player_movement.y = -1.;
         } else {
-            player_movement.y = actions.player_movement.unwrap_or(Vec2::ZERO).y;
+            player_movement.y = keyboard_movement.y;
         }
 
-        if GameControl::Right.just_released(&keyboard_input)
-            || GameControl::Left.just_released(&keyboard_input)
+        if GameControl::Right.just_released(&keymap, &keyboard_input)
+            || GameControl::Left.just_released(&keymap, &keyboard_input)
         {
-            if GameControl::Right.pressed(&keyboard_input) {
+            if GameControl::Right.pressed(&keymap, &keyboard_input) {
                 player_movement.x = 1.;
-            } else if GameControl::Left.pressed(&keyboard_input) {
+            } else if GameControl::Left.pressed(&keymap, &keyboard_input) {
                 player_movement.x = -1.;
             } else {
                 player_movement.x = 0.;
             }
-        } else if GameControl::Right.just_pressed(&keyboard_input) {
+        } else if GameControl::Right.just_pressed(&keymap, &keyboard_input) {
             player_movement.x = 1.;
-        } else if GameControl::Left.just_pressed(&keyboard_input) {
+        } else if GameControl::Left.just_pressed(&keymap, &keyboard_input) {
             player_movement.x = -1.;
         } else {
-            player_movement.x = actions.player_movement.unwrap_or(Vec2::ZERO).x;
+            player_movement.x = keyboard_movement.x;
         }
 
-        if player_movement != Vec2::ZERO {
-            player_movement = player_movement.normalize();
-            actions.player_movement = Some(player_movement);
-        }
+        *keyboard_movement = player_movement;
+        actions.player_movement = player_movement.try_normalize();
     } else {
+        *keyboard_movement = Vec2::ZERO;
         actions.player_movement = None;
     }
 }
 
-enum GameControl {
+/// Adds the input of every connected gamepad on top of the keyboard one.
+/// The keyboard wins when both ask for a movement.
+fn set_gamepad_actions(
+    mut actions: ResMut<Actions>,
+    gamepads: Res<Gamepads>,
+    gamepad_input: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+) {
+    for gamepad in gamepads.iter() {
+        let button = |button_type| GamepadButton::new(*gamepad, button_type);
+        if gamepad_input.just_pressed(button(GamepadButtonType::South)) {
+            actions.player_jump = Some(true);
+        }
+        actions.player_jump_held |= gamepad_input.pressed(button(GamepadButtonType::South));
+        if gamepad_input.just_pressed(button(GamepadButtonType::West)) {
+            actions.build_tower = Some(true);
+        }
+        if gamepad_input.just_pressed(button(GamepadButtonType::East)) {
+            actions.player_attack = Some(true);
+        }
+
+        if actions.player_movement.is_some() {
+            continue;
+        }
+        let direction = |negative, positive| {
+            gamepad_input.pressed(button(positive)) as i32 as f32
+                - gamepad_input.pressed(button(negative)) as i32 as f32
+        };
+        let axis = |axis_type| {
+            gamepad_axes
+                .get(GamepadAxis::new(*gamepad, axis_type))
+                .unwrap_or(0.)
+        };
+        let dpad = Vec2::new(
+            direction(GamepadButtonType::DPadLeft, GamepadButtonType::DPadRight),
+            direction(GamepadButtonType::DPadDown, GamepadButtonType::DPadUp),
+        );
+        let movement = match dpad.try_normalize() {
+            Some(dpad) => dpad,
+            None => apply_deadzone(Vec2::new(
+                axis(GamepadAxisType::LeftStickX),
+                axis(GamepadAxisType::LeftStickY),
+            )),
+        };
+        if movement != Vec2::ZERO {
+            actions.player_movement = Some(movement);
+        }
+    }
+}
+
+/// Ignores the stick near its center and rescales the rest, so the movement grows from 0
+/// at the edge of the deadzone to 1 when the stick is fully tilted
+fn apply_deadzone(stick: Vec2) -> Vec2 {
+    let length = stick.length();
+    if length < STICK_DEADZONE {
+        return Vec2::ZERO;
+    }
+    stick / length * ((length - STICK_DEADZONE) / (1. - STICK_DEADZONE)).min(1.)
+}
+
+fn log_gamepad_connections(mut gamepad_events: EventReader<GamepadEvent>) {
+    for event in gamepad_events.iter() {
+        match event.event_type {
+            GamepadEventType::Connected => info!("Gamepad {} connected", event.gamepad.id),
+            GamepadEventType::Disconnected => {
+                info!("Gamepad {} disconnected", event.gamepad.id)
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The logical actions a key can be bound to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum GameControl {
     Up,
     Down,
     Left,
     Right,
+    Jump,
+    Build,
+    Attack,
+    Pause,
 }
 
 impl GameControl {
-    fn just_released(&self, keyboard_input: &Res<Input<KeyCode>>) -> bool {
+    pub const ALL: [GameControl; 8] = [
+        GameControl::Up,
+        GameControl::Down,
+        GameControl::Left,
+        GameControl::Right,
+        GameControl::Jump,
+        GameControl::Build,
+        GameControl::Attack,
+        GameControl::Pause,
+    ];
+
+    pub fn label(self) -> &'static str {
         match self {
-            GameControl::Up => {
-                keyboard_input.just_released(KeyCode::W)
-                    || keyboard_input.just_released(KeyCode::Up)
-            }
-            GameControl::Down => {
-                keyboard_input.just_released(KeyCode::S)
-                    || keyboard_input.just_released(KeyCode::Down)
-            }
-            GameControl::Left => {
-                keyboard_input.just_released(KeyCode::A)
-                    || keyboard_input.just_released(KeyCode::Left)
-            }
-            GameControl::Right => {
-                keyboard_input.just_released(KeyCode::D)
-                    || keyboard_input.just_released(KeyCode::Right)
-            }
+            GameControl::Up => "Up",
+            GameControl::Down => "Down",
+            GameControl::Left => "Left",
+            GameControl::Right => "Right",
+            GameControl::Jump => "Jump",
+            GameControl::Build => "Build",
+            GameControl::Attack => "Attack",
+            GameControl::Pause => "Pause",
         }
     }
 
-    fn pressed(&self, keyboard_input: &Res<Input<KeyCode>>) -> bool {
-        match self {
-            GameControl::Up => {
-                keyboard_input.pressed(KeyCode::W) || keyboard_input.pressed(KeyCode::Up)
-            }
-            GameControl::Down => {
-                keyboard_input.pressed(KeyCode::S) || keyboard_input.pressed(KeyCode::Down)
-            }
-            GameControl::Left => {
-                keyboard_input.pressed(KeyCode::A) || keyboard_input.pressed(KeyCode::Left)
-            }
-            GameControl::Right => {
-                keyboard_input.pressed(KeyCode::D) || keyboard_input.pressed(KeyCode::Right)
-            }
+    pub fn just_released(&self, keymap: &Keymap, keyboard_input: &Input<KeyCode>) -> bool {
+        keymap
+            .keys(*self)
+            .iter()
+            .any(|key| keyboard_input.just_released(*key))
+    }
+
+    pub fn pressed(&self, keymap: &Keymap, keyboard_input: &Input<KeyCode>) -> bool {
+        keymap
+            .keys(*self)
+            .iter()
+            .any(|key| keyboard_input.pressed(*key))
+    }
+
+    pub fn just_pressed(&self, keymap: &Keymap, keyboard_input: &Input<KeyCode>) -> bool {
+        keymap
+            .keys(*self)
+            .iter()
+            .any(|key| keyboard_input.just_pressed(*key))
+    }
+}
+
+const KEYMAP_STORAGE_KEY: &str = "keymap";
+
+/// The keys bound to every [GameControl]. A control can have any number of keys.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Keymap {
+    bindings: BTreeMap<GameControl, Vec<KeyCode>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = [
+            (GameControl::Up, vec![KeyCode::W, KeyCode::Up]),
+            (GameControl::Down, vec![KeyCode::S, KeyCode::Down]),
+            (GameControl::Left, vec![KeyCode::A, KeyCode::Left]),
+            (GameControl::Right, vec![KeyCode::D, KeyCode::Right]),
+            (
+                GameControl::Jump,
+                vec![KeyCode::W, KeyCode::Up, KeyCode::Space],
+            ),
+            (GameControl::Build, vec![KeyCode::E]),
+            (GameControl::Attack, vec![KeyCode::F]),
+            (GameControl::Pause, vec![KeyCode::Escape]),
+        ];
+        Self {
+            bindings: bindings.into_iter().collect(),
         }
     }
+}
 
-    fn just_pressed(&self, keyboard_input: &Res<Input<KeyCode>>) -> bool {
-        match self {
-            GameControl::Up => {
-                keyboard_input.just_pressed(KeyCode::W)
-                    || keyboard_input.just_pressed(KeyCode::Up)
-                    || keyboard_input.just_pressed(KeyCode::Space)
-            }
-            GameControl::Down => {
-                keyboard_input.just_pressed(KeyCode::S)
-                    || keyboard_input.just_pressed(KeyCode::Down)
-            }
-            GameControl::Left => {
-                keyboard_input.just_pressed(KeyCode::A)
-                    || keyboard_input.just_pressed(KeyCode::Left)
-            }
-            GameControl::Right => {
-                keyboard_input.just_pressed(KeyCode::D)
-                    || keyboard_input.just_pressed(KeyCode::Right)
+impl Keymap {
+    pub fn keys(&self, control: GameControl) -> &[KeyCode] {
+        self.bindings.get(&control).map_or(&[], Vec::as_slice)
+    }
+
+    /// Replaces every key of `control` with `key`
+    pub fn set(&mut self, control: GameControl, key: KeyCode) {
+        self.bindings.insert(control, vec![key]);
+    }
+
+    /// Binds one more key to `control`
+    pub fn add(&mut self, control: GameControl, key: KeyCode) {
+        let keys = self.bindings.entry(control).or_default();
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+
+    /// The saved keymap, or the default one when none was saved or it can't be read.
+    /// Controls missing from the saved keymap keep their default keys.
+    pub fn load() -> Self {
+        let mut keymap = Self::default();
+        if let Some(contents) = storage::load(KEYMAP_STORAGE_KEY) {
+            match ron::from_str::<Keymap>(&contents) {
+                Ok(saved) => keymap.bindings.extend(saved.bindings),
+                Err(err) => warn!("Ignoring the saved keymap: {err}"),
             }
         }
+        keymap
+    }
+
+    pub fn save(&self) {
+        let result = ron::ser::to_string_pretty(self, default())
+            .map_err(|err| err.to_string())
+            .and_then(|contents| storage::save(KEYMAP_STORAGE_KEY, &contents));
+        if let Err(err) = result {
+            warn!("Could not save the keymap: {err}");
+        }
     }
 }
@@ -1,7 +1,7 @@
 use crate::loading::AudioAssets;
 use crate::GameState;
 use bevy::prelude::*;
-use bevy_kira_audio::{Audio, AudioPlugin};
+use bevy_kira_audio::{Audio, AudioApp, AudioChannel, AudioPlugin};
 
 pub struct InternalAudioPlugin;
 
@@ -9,12 +9,73 @@ pub struct InternalAudioPlugin;
 impl Plugin for InternalAudioPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(AudioPlugin)
-            .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(start_audio));
+            .add_audio_channel::<EffectsChannel>()
+            .init_resource::<Volume>()
+            .add_event::<SoundEffect>()
+            .add_system(apply_volume)
+            .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(start_audio))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing).with_system(play_sound_effects),
+            )
+            .add_system_set(SystemSet::on_pause(GameState::Playing).with_system(pause_effects))
+            .add_system_set(SystemSet::on_resume(GameState::Playing).with_system(resume_effects));
+    }
+}
+
+/// Sound effects requested by the gameplay systems.
+/// Gameplay only sends these events, so it keeps working when no audio backend is present.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SoundEffect {
+    Attack,
+    Collect,
+}
+
+/// Sound effects play on their own channel, so they can be paused without touching the music
+struct EffectsChannel;
+
+/// Volume of every channel, from 0 to 1
+pub struct Volume(pub f32);
+
+impl Default for Volume {
+    fn default() -> Self {
+        Self(0.3)
+    }
+}
+
+fn apply_volume(
+    volume: Res<Volume>,
+    audio: Res<Audio>,
+    effects: Res<AudioChannel<EffectsChannel>>,
+) {
+    if volume.is_changed() {
+        audio.set_volume(volume.0);
+        effects.set_volume(volume.0);
     }
 }
 
 fn start_audio(audio_assets: Res<AudioAssets>, audio: Res<Audio>) {
-    audio.set_volume(0.3);
     audio.play_looped(audio_assets.flying.clone());
     audio.pause();
 }
+
+fn play_sound_effects(
+    mut sound_effects: EventReader<SoundEffect>,
+    audio_assets: Res<AudioAssets>,
+    effects: Res<AudioChannel<EffectsChannel>>,
+) {
+    for sound_effect in sound_effects.iter() {
+        let source = match sound_effect {
+            SoundEffect::Attack => audio_assets.attack.clone(),
+            SoundEffect::Collect => audio_assets.collect.clone(),
+        };
+        effects.play(source);
+    }
+}
+
+fn pause_effects(effects: Res<AudioChannel<EffectsChannel>>) {
+    effects.pause();
+}
+
+fn resume_effects(effects: Res<AudioChannel<EffectsChannel>>) {
+    effects.resume();
+}
@@ -0,0 +1,57 @@
+use crate::GameState;
+use bevy::prelude::*;
+
+/// Despawns, with its children, any entity tagged with [DespawnOnExit] when its state is exited.
+/// Pausing a state (pushing another one on top of it) keeps its entities.
+pub struct CleanupPlugin;
+
+impl Plugin for CleanupPlugin {
+    fn build(&self, app: &mut App) {
+        for state in GameState::ALL {
+            app.add_system_set(
+                SystemSet::on_exit(state.clone()).with_system(despawn_on_exit(state)),
+            );
+        }
+    }
+}
+
+/// The entity only lives while the game is in the given state
+#[derive(Component)]
+pub struct DespawnOnExit(pub GameState);
+
+fn despawn_on_exit(state: GameState) -> impl FnMut(Commands, Query<(Entity, &DespawnOnExit)>) {
+    move |mut commands, query| {
+        for (entity, scope) in query.iter() {
+            if scope.0 == state {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+pub trait ResetOnEnterAppExt {
+    /// Puts a fresh resource, built with [FromWorld], in place every time `state` is entered,
+    /// before the other systems entering it run
+    fn reset_on_enter<R: FromWorld + Send + Sync + 'static>(
+        &mut self,
+        state: GameState,
+    ) -> &mut Self;
+}
+
+impl ResetOnEnterAppExt for App {
+    fn reset_on_enter<R: FromWorld + Send + Sync + 'static>(
+        &mut self,
+        state: GameState,
+    ) -> &mut Self {
+        self.add_system_set(
+            SystemSet::on_enter(state).with_system(
+                (|world: &mut World| {
+                    let resource = R::from_world(world);
+                    world.insert_resource(resource);
+                })
+                .exclusive_system()
+                .at_start(),
+            ),
+        )
+    }
+}
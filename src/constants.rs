@@ -1,2 +0,0 @@
-pub const WIN_WIDTH: f32 = 800.;
-pub const WIN_HEIGHT: f32 = 600.;
@@ -0,0 +1,322 @@
+use crate::game::GameCamera;
+use crate::storage;
+use bevy::prelude::*;
+use bevy::window::WindowMode;
+use serde::{Deserialize, Serialize};
+
+pub struct DisplayPlugin;
+
+/// This plugin spawns the game camera and fits the game to the window, whatever its size:
+/// the camera shows the [DisplaySettings] virtual resolution scaled by [ViewArea::scale],
+/// and the HUD follows. F11 switches between a window and fullscreen.
+impl Plugin for DisplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DisplaySettings>()
+            .init_resource::<ViewArea>()
+            .add_startup_system(setup_camera)
+            .add_system(toggle_fullscreen)
+            .add_system(apply_window_mode.after(toggle_fullscreen))
+            .add_system(spawn_letterbox_bars)
+            .add_system(fit_view.after(spawn_letterbox_bars))
+            .add_system(scale_texts.after(fit_view))
+            .add_system(anchor_to_view.after(fit_view));
+    }
+}
+
+const DISPLAY_STORAGE_KEY: &str = "display";
+/// Side of the letterbox bars, in virtual pixels, enough to cover any window
+const BAR_SIZE: f32 = 100_000.;
+
+/// How the virtual resolution is fitted into the window
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ViewScaling {
+    /// Scaled as much as the window allows, the rest is covered with black bars
+    Letterbox,
+    /// Scaled like [ViewScaling::Letterbox], but the extra room shows more of the world
+    Expand,
+    /// Scaled by a whole factor for crisp pixels, with black bars around.
+    /// Windows smaller than the virtual resolution fall back to [ViewScaling::Letterbox].
+    Integer,
+}
+
+impl ViewScaling {
+    pub fn label(self) -> &'static str {
+        match self {
+            ViewScaling::Letterbox => "letterbox",
+            ViewScaling::Expand => "expand",
+            ViewScaling::Integer => "integer scale",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            ViewScaling::Letterbox => ViewScaling::Expand,
+            ViewScaling::Expand => ViewScaling::Integer,
+            ViewScaling::Integer => ViewScaling::Letterbox,
+        }
+    }
+}
+
+/// How the game is shown, saved between two launches.
+/// Changing the resource applies right away, call [DisplaySettings::save] to keep the change.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplaySettings {
+    /// Size of the view the game and its HUD are laid out for, in pixels.
+    /// The window opens at this size.
+    pub virtual_width: f32,
+    pub virtual_height: f32,
+    pub scaling: ViewScaling,
+    pub fullscreen: bool,
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self {
+            virtual_width: 800.,
+            virtual_height: 600.,
+            scaling: ViewScaling::Letterbox,
+            fullscreen: false,
+        }
+    }
+}
+
+impl DisplaySettings {
+    /// The saved settings, or the default ones when none were saved or they can't be read
+    pub fn load() -> Self {
+        let mut settings: Self = storage::load(DISPLAY_STORAGE_KEY)
+            .and_then(|contents| {
+                ron::from_str(&contents)
+                    .map_err(|err| warn!("Ignoring the saved display settings: {err}"))
+                    .ok()
+            })
+            .unwrap_or_default();
+        let size = settings.virtual_size();
+        if !(size.is_finite() && size.cmpgt(Vec2::ZERO).all()) {
+            warn!(
+                "Ignoring the saved virtual resolution {}x{}, it must be positive",
+                settings.virtual_width, settings.virtual_height
+            );
+            let default = Self::default();
+            settings.virtual_width = default.virtual_width;
+            settings.virtual_height = default.virtual_height;
+        }
+        settings
+    }
+
+    pub fn save(&self) {
+        let result = ron::ser::to_string_pretty(self, default())
+            .map_err(|err| err.to_string())
+            .and_then(|contents| storage::save(DISPLAY_STORAGE_KEY, &contents));
+        if let Err(err) = result {
+            warn!("Could not save the display settings: {err}");
+        }
+    }
+
+    pub fn virtual_size(&self) -> Vec2 {
+        Vec2::new(self.virtual_width, self.virtual_height)
+    }
+
+    pub fn window_mode(&self) -> WindowMode {
+        if self.fullscreen {
+            WindowMode::BorderlessFullscreen
+        } else {
+            WindowMode::Windowed
+        }
+    }
+
+    /// The window the game opens in
+    pub fn window_descriptor(&self, title: &str) -> WindowDescriptor {
+        WindowDescriptor {
+            width: self.virtual_width,
+            height: self.virtual_height,
+            mode: self.window_mode(),
+            title: title.to_string(),
+            ..default()
+        }
+    }
+}
+
+/// Where the virtual resolution ends up in the window, updated as the window or the
+/// [DisplaySettings] change
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ViewArea {
+    /// Window pixels per virtual pixel
+    pub scale: f32,
+    /// Window pixels between each edge of the window and the part the game is shown in,
+    /// covered by the letterbox bars
+    pub insets: UiRect<f32>,
+    /// Size of the part of the world left visible by the letterbox bars, in world pixels
+    pub visible: Vec2,
+}
+
+impl Default for ViewArea {
+    fn default() -> Self {
+        Self {
+            scale: 1.,
+            insets: UiRect::all(0.),
+            visible: DisplaySettings::default().virtual_size(),
+        }
+    }
+}
+
+impl ViewArea {
+    pub fn fit(settings: &DisplaySettings, window: Vec2) -> Self {
+        let fit = (window / settings.virtual_size()).min_element();
+        let scale = match settings.scaling {
+            ViewScaling::Integer if fit >= 1. => fit.floor(),
+            _ => fit,
+        };
+        let visible = match settings.scaling {
+            ViewScaling::Expand => window / scale,
+            ViewScaling::Letterbox | ViewScaling::Integer => settings.virtual_size(),
+        };
+        let bars = (window - visible * scale) / 2.;
+        Self {
+            scale,
+            insets: UiRect::new(bars.x, bars.x, bars.y, bars.y),
+            visible,
+        }
+    }
+}
+
+/// Covers one side of the window outside of the virtual resolution
+#[derive(Component)]
+struct LetterboxBar(Vec2);
+
+/// Scales the font size of every section of this text with [ViewArea::scale]
+#[derive(Component, Default)]
+pub struct ScaledText {
+    /// Font sizes as the text was spawned with
+    base: Vec<f32>,
+}
+
+/// Keeps an absolutely positioned node at the same place of the view: its `Val::Px` offsets
+/// are scaled with [ViewArea::scale] and counted from the letterbox bars
+#[derive(Component, Default)]
+pub struct AnchoredToView {
+    /// Offsets as the node was spawned with
+    base: Option<UiRect<Val>>,
+}
+
+fn setup_camera(mut commands: Commands) {
+    commands
+        .spawn_bundle(Camera2dBundle::default())
+        .insert(GameCamera::default());
+}
+
+fn toggle_fullscreen(keyboard_input: Res<Input<KeyCode>>, mut settings: ResMut<DisplaySettings>) {
+    if keyboard_input.just_pressed(KeyCode::F11) {
+        settings.fullscreen = !settings.fullscreen;
+        settings.save();
+    }
+}
+
+fn apply_window_mode(settings: Res<DisplaySettings>, mut windows: ResMut<Windows>) {
+    if !settings.is_changed() {
+        return;
+    }
+    if let Some(window) = windows.get_primary_mut() {
+        if window.mode() != settings.window_mode() {
+            window.set_mode(settings.window_mode());
+        }
+    }
+}
+
+fn spawn_letterbox_bars(mut commands: Commands, camera: Query<Entity, Added<GameCamera>>) {
+    for camera in camera.iter() {
+        commands.entity(camera).with_children(|parent| {
+            for side in [Vec2::X, Vec2::NEG_X, Vec2::Y, Vec2::NEG_Y] {
+                parent
+                    .spawn_bundle(SpriteBundle {
+                        sprite: Sprite {
+                            color: Color::BLACK,
+                            custom_size: Some(Vec2::splat(BAR_SIZE)),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .insert(LetterboxBar(side));
+            }
+        });
+    }
+}
+
+fn fit_view(
+    windows: Res<Windows>,
+    settings: Res<DisplaySettings>,
+    mut view: ResMut<ViewArea>,
+    mut camera: Query<&mut OrthographicProjection, With<GameCamera>>,
+    mut bars: Query<(
+        &mut Transform,
+        &mut Visibility,
+        &LetterboxBar,
+        ChangeTrackers<LetterboxBar>,
+    )>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => Vec2::new(window.width(), window.height()),
+        None => return,
+    };
+    // Minimized windows have no size to fit into
+    if window.min_element() <= 0. {
+        return;
+    }
+    let fitted = ViewArea::fit(&settings, window);
+    if *view != fitted {
+        *view = fitted;
+    }
+    for mut projection in camera.iter_mut() {
+        if projection.scale != 1. / view.scale {
+            projection.scale = 1. / view.scale;
+        }
+    }
+
+    let shown = settings.scaling != ViewScaling::Expand;
+    for (mut transform, mut visibility, LetterboxBar(side), bar) in bars.iter_mut() {
+        if !settings.is_changed() && !bar.is_added() {
+            continue;
+        }
+        // In front of the camera, over everything in the world
+        let offset = (settings.virtual_size() + BAR_SIZE) / 2. * *side;
+        transform.translation = offset.extend(-1.);
+        visibility.is_visible = shown;
+    }
+}
+
+fn scale_texts(view: Res<ViewArea>, mut texts: Query<(&mut Text, &mut ScaledText)>) {
+    for (mut text, mut scaled) in texts.iter_mut() {
+        if scaled.base.is_empty() {
+            scaled.base = text
+                .sections
+                .iter()
+                .map(|section| section.style.font_size)
+                .collect();
+        } else if !view.is_changed() {
+            continue;
+        }
+        for (section, base) in text.sections.iter_mut().zip(&scaled.base) {
+            section.style.font_size = base * view.scale;
+        }
+    }
+}
+
+fn anchor_to_view(view: Res<ViewArea>, mut nodes: Query<(&mut Style, &mut AnchoredToView)>) {
+    for (mut style, mut anchored) in nodes.iter_mut() {
+        let base = match anchored.base {
+            Some(_) if !view.is_changed() => continue,
+            Some(base) => base,
+            None => *anchored.base.insert(style.position),
+        };
+        let place = |offset: Val, inset: f32| match offset {
+            Val::Px(offset) => Val::Px(inset + offset * view.scale),
+            other => other,
+        };
+        style.position = UiRect {
+            left: place(base.left, view.insets.left),
+            right: place(base.right, view.insets.right),
+            top: place(base.top, view.insets.top),
+            bottom: place(base.bottom, view.insets.bottom),
+        };
+    }
+}
@@ -0,0 +1,424 @@
+use crate::cleanup::{DespawnOnExit, ResetOnEnterAppExt};
+use crate::display::ViewArea;
+use crate::game::{clamp_to_level, GameCamera, Level, Levels, Tile, TileRect};
+use crate::loading::FontAssets;
+use crate::GameState;
+use bevy::asset::AssetServerSettings;
+use bevy::prelude::*;
+use bevy::render::camera::Camera;
+use bevy_prototype_lyon::prelude::*;
+
+pub struct EditorPlugin;
+
+/// This plugin lets designers build levels inside the game, in `GameState::Editor`.
+/// The level picked in the menu is edited in place, so a playtest is a regular run of it.
+/// F2 switches between the editor and a run of the edited level.
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.reset_on_enter::<Editor>(GameState::Editor)
+            .add_system(toggle_editor)
+            .add_system_set(
+                SystemSet::on_enter(GameState::Editor)
+                    .with_system(setup_editor)
+                    .with_system(center_view),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Editor)
+                    .with_system(pick_brush)
+                    .with_system(edit_level)
+                    .with_system(editor_commands)
+                    .with_system(pan_view)
+                    .with_system(draw_level.after(edit_level))
+                    .with_system(update_editor_text),
+            );
+    }
+}
+
+/// Tiles painted with the left mouse button, picked with the number keys
+const BRUSHES: [(KeyCode, Tile, &str); 3] = [
+    (KeyCode::Key1, Tile::Solid, "solid"),
+    (KeyCode::Key2, Tile::OneWay, "one-way"),
+    (KeyCode::Key3, Tile::EnemySpawn, "enemy spawn"),
+];
+
+/// Speed the arrow keys scroll the view at, in pixels per second
+const PAN_SPEED: f32 = 600.;
+
+#[derive(Default)]
+struct Editor {
+    /// Index in [BRUSHES]
+    brush: usize,
+    drag: Option<Drag>,
+    /// Rows of the level as last drawn, the level is drawn again when they differ
+    drawn: Vec<String>,
+    /// Result of the last playtest or save
+    status: String,
+}
+
+enum Drag {
+    /// The player start or the core, following the hovered tile
+    Marker(Tile),
+    /// A zone of enemy spawn tiles, moved by as many tiles as the cursor
+    Zone {
+        rect: TileRect,
+        from: (usize, usize),
+    },
+}
+
+#[derive(Component)]
+struct EditorTile;
+
+#[derive(Component)]
+struct EditorText;
+
+fn toggle_editor(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut state: ResMut<State<GameState>>,
+    mut levels: ResMut<Levels>,
+    mut editor: ResMut<Editor>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F2) {
+        return;
+    }
+    match state.current() {
+        GameState::Menu | GameState::Playing => {
+            // Fails when a menu button was clicked, or the run paused or ended, during the same
+            // frame. F2 has to be pressed again then.
+            let _ = state.set(GameState::Editor);
+        }
+        GameState::Editor => playtest(&mut state, &mut levels, &mut editor),
+        _ => {}
+    }
+}
+
+/// Starts a run of the edited level, once it went through the same checks as a loaded one
+fn playtest(state: &mut State<GameState>, levels: &mut Levels, editor: &mut Editor) {
+    let selected = levels.selected;
+    match reload(&levels.levels[selected]) {
+        Ok(level) => {
+            levels.levels[selected] = level;
+            // Fails when F2 and Enter are pressed during the same frame, one run starts either way
+            let _ = state.set(GameState::Playing);
+        }
+        Err(message) => editor.status = message,
+    }
+}
+
+/// The level as the loader would read it back from its file
+fn reload(level: &Level) -> Result<Level, String> {
+    Level::from_ron(level.to_ron().as_bytes(), &level.path)
+}
+
+fn setup_editor(mut commands: Commands, font_assets: Res<FontAssets>) {
+    let text_style = |font_size| TextStyle {
+        font: font_assets.fira_sans.clone(),
+        font_size,
+        color: Color::rgb(0.9, 0.9, 0.9),
+    };
+    commands
+        .spawn_bundle(
+            TextBundle::from_sections([
+                TextSection::new("", text_style(20.0)),
+                TextSection::new(
+                    "\nLeft click paint, drag P, C and spawn zones, right click erase\n\
+                     Arrows scroll, Enter or F2 playtest, Ctrl+S save, Esc menu",
+                    text_style(16.0),
+                ),
+                TextSection::new("", text_style(16.0)),
+            ])
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(10.0),
+                    top: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(DespawnOnExit(GameState::Editor))
+        .insert(EditorText);
+}
+
+fn pick_brush(keyboard_input: Res<Input<KeyCode>>, mut editor: ResMut<Editor>) {
+    if let Some(index) = BRUSHES
+        .iter()
+        .position(|(key, _, _)| keyboard_input.just_pressed(*key))
+    {
+        editor.brush = index;
+    }
+}
+
+/// Opens on the middle of the level, wherever the last run left the camera
+fn center_view(
+    levels: Res<Levels>,
+    view: Res<ViewArea>,
+    mut camera: Query<&mut Transform, With<GameCamera>>,
+) {
+    for mut transform in camera.iter_mut() {
+        let center = clamp_to_level(Vec2::ZERO, view.visible, levels.current());
+        transform.translation = center.extend(transform.translation.z);
+    }
+}
+
+/// Scrolls through levels larger than the window, the view never leaves the level
+fn pan_view(
+    time: Res<Time>,
+    keyboard_input: Res<Input<KeyCode>>,
+    levels: Res<Levels>,
+    view: Res<ViewArea>,
+    mut camera: Query<&mut Transform, With<GameCamera>>,
+) {
+    let axis = |negative, positive| {
+        keyboard_input.pressed(positive) as i8 as f32
+            - keyboard_input.pressed(negative) as i8 as f32
+    };
+    let direction = Vec2::new(
+        axis(KeyCode::Left, KeyCode::Right),
+        axis(KeyCode::Down, KeyCode::Up),
+    );
+    for mut transform in camera.iter_mut() {
+        let center =
+            transform.translation.truncate() + direction * PAN_SPEED * time.delta_seconds();
+        let center = clamp_to_level(center, view.visible, levels.current());
+        transform.translation = center.extend(transform.translation.z);
+    }
+}
+
+/// Where the cursor points in the world, seen through the game camera
+fn cursor_position(
+    windows: &Windows,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+) -> Option<Vec2> {
+    let window = windows.get_primary()?;
+    let cursor = window.cursor_position()?;
+    let ndc = cursor / Vec2::new(window.width(), window.height()) * 2. - Vec2::ONE;
+    let ndc_to_world = camera_transform.compute_matrix() * camera.projection_matrix().inverse();
+    Some(ndc_to_world.project_point3(ndc.extend(-1.)).truncate())
+}
+
+fn edit_level(
+    windows: Res<Windows>,
+    mouse_input: Res<Input<MouseButton>>,
+    camera: Query<(&Camera, &GlobalTransform)>,
+    mut levels: ResMut<Levels>,
+    mut editor: ResMut<Editor>,
+) {
+    if !mouse_input.pressed(MouseButton::Left) {
+        editor.drag = None;
+    }
+    let (camera, camera_transform) = match camera.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let hovered = cursor_position(&windows, camera, camera_transform)
+        .and_then(|position| levels.current().tile_at(position));
+    let (column, row) = match hovered {
+        Some(hovered) => hovered,
+        None => return,
+    };
+    let hovered_tile = levels.current().tile(column, row);
+    let selected = levels.selected;
+    let level = &mut levels.levels[selected];
+
+    if mouse_input.just_pressed(MouseButton::Left) {
+        let (_, brush, _) = BRUSHES[editor.brush];
+        editor.drag = match hovered_tile {
+            Tile::PlayerStart | Tile::Core => Some(Drag::Marker(hovered_tile)),
+            Tile::EnemySpawn if brush == Tile::EnemySpawn => level
+                .merge(Tile::EnemySpawn, true)
+                .into_iter()
+                .find(|rect| rect.contains(column, row))
+                .map(|rect| Drag::Zone {
+                    rect,
+                    from: (column, row),
+                }),
+            _ => None,
+        };
+    }
+
+    if mouse_input.pressed(MouseButton::Right) {
+        if !matches!(hovered_tile, Tile::PlayerStart | Tile::Core) {
+            level.set_tile(column, row, Tile::Empty);
+        }
+        return;
+    }
+    if !mouse_input.pressed(MouseButton::Left) {
+        return;
+    }
+    match &mut editor.drag {
+        Some(Drag::Marker(marker)) => {
+            if hovered_tile == Tile::Empty {
+                if let Some((old_column, old_row)) = level.find(*marker) {
+                    level.set_tile(old_column, old_row, Tile::Empty);
+                }
+                level.set_tile(column, row, *marker);
+            }
+        }
+        Some(Drag::Zone { rect, from }) => {
+            if let Some(moved) = move_zone(level, *rect, *from, (column, row)) {
+                *rect = moved;
+                *from = (column, row);
+            }
+        }
+        None => {
+            let (_, brush, _) = BRUSHES[editor.brush];
+            if !matches!(hovered_tile, Tile::PlayerStart | Tile::Core) {
+                level.set_tile(column, row, brush);
+            }
+        }
+    }
+}
+
+/// Moves a zone of enemy spawn tiles by the cursor's motion, as long as it only covers
+/// empty tiles and itself at its new place
+fn move_zone(
+    level: &mut Level,
+    rect: TileRect,
+    from: (usize, usize),
+    to: (usize, usize),
+) -> Option<TileRect> {
+    if from == to {
+        return None;
+    }
+    let column = (rect.column + to.0).checked_sub(from.0)?;
+    let row = (rect.row + to.1).checked_sub(from.1)?;
+    let moved = TileRect {
+        column,
+        row,
+        ..rect
+    };
+    if moved.column + moved.width > level.width() || moved.row + moved.height > level.height() {
+        return None;
+    }
+    let tiles = |rect: TileRect| {
+        (rect.row..rect.row + rect.height)
+            .flat_map(move |row| (rect.column..rect.column + rect.width).map(move |c| (c, row)))
+    };
+    let free = tiles(moved).all(|(c, r)| level.tile(c, r) == Tile::Empty || rect.contains(c, r));
+    if !free {
+        return None;
+    }
+    for (c, r) in tiles(rect) {
+        level.set_tile(c, r, Tile::Empty);
+    }
+    for (c, r) in tiles(moved) {
+        level.set_tile(c, r, Tile::EnemySpawn);
+    }
+    Some(moved)
+}
+
+fn editor_commands(
+    keyboard_input: Res<Input<KeyCode>>,
+    asset_settings: Res<AssetServerSettings>,
+    mut state: ResMut<State<GameState>>,
+    mut levels: ResMut<Levels>,
+    mut editor: ResMut<Editor>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        // Fails when F2 was pressed during the same frame, the playtest wins then
+        let _ = state.set(GameState::Menu);
+    } else if keyboard_input.just_pressed(KeyCode::Return) {
+        playtest(&mut state, &mut levels, &mut editor);
+    } else if keyboard_input.just_pressed(KeyCode::S)
+        && (keyboard_input.pressed(KeyCode::LControl) || keyboard_input.pressed(KeyCode::RControl))
+    {
+        let saved = reload(levels.current()).and_then(|level| save(&level, &asset_settings));
+        editor.status = match saved {
+            Ok(path) => format!("Saved to {path}"),
+            Err(message) => message,
+        };
+    }
+}
+
+/// Writes the level over the file it was loaded from, returns where it went
+#[cfg(not(target_arch = "wasm32"))]
+fn save(level: &Level, asset_settings: &AssetServerSettings) -> Result<String, String> {
+    let path = bevy::asset::FileAssetIo::get_base_path()
+        .join(&asset_settings.asset_folder)
+        .join(&level.path);
+    std::fs::write(&path, level.to_ron()).map_err(|err| format!("Could not save: {err}"))?;
+    info!("Saved level {} to {}", level.name, path.display());
+    Ok(path.display().to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn save(_level: &Level, _asset_settings: &AssetServerSettings) -> Result<String, String> {
+    Err("Levels can only be saved from the desktop build".to_string())
+}
+
+fn draw_level(
+    mut commands: Commands,
+    mut editor: ResMut<Editor>,
+    levels: Res<Levels>,
+    tiles: Query<Entity, With<EditorTile>>,
+) {
+    let level = levels.current();
+    if editor.drawn == level.rows {
+        return;
+    }
+    editor.drawn = level.rows.clone();
+    for entity in tiles.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    let mut spawn = |min: Vec2, max: Vec2, z: f32, color: Color| {
+        let shape = shapes::Rectangle {
+            extents: max - min,
+            ..default()
+        };
+        commands
+            .spawn_bundle(GeometryBuilder::build_as(
+                &shape,
+                DrawMode::Fill(bevy_prototype_lyon::prelude::FillMode::color(color)),
+                Transform::from_translation(((min + max) / 2.).extend(z)),
+            ))
+            .insert(EditorTile)
+            .insert(DespawnOnExit(GameState::Editor));
+    };
+    spawn(
+        -level.size() / 2.,
+        level.size() / 2.,
+        0.,
+        Color::rgb(0.2, 0.2, 0.25),
+    );
+    for row in 0..level.height() {
+        for column in 0..level.width() {
+            let (min, max) = level.bounds(TileRect {
+                column,
+                row,
+                width: 1,
+                height: 1,
+            });
+            let color = match level.tile(column, row) {
+                Tile::Empty => continue,
+                Tile::Solid => Color::BLACK,
+                Tile::OneWay => {
+                    let min = Vec2::new(min.x, max.y - level.tile_size / 4.);
+                    spawn(min, max, 1., Color::DARK_GRAY);
+                    continue;
+                }
+                Tile::PlayerStart => Color::CYAN,
+                Tile::Core => Color::GOLD,
+                Tile::EnemySpawn => Color::rgba(0.9, 0.2, 0.2, 0.4),
+            };
+            spawn(min, max, 1., color);
+        }
+    }
+}
+
+fn update_editor_text(
+    mut text: Query<&mut Text, With<EditorText>>,
+    editor: Res<Editor>,
+    levels: Res<Levels>,
+) {
+    let (_, _, brush) = BRUSHES[editor.brush];
+    for mut text in &mut text {
+        text.sections[0].value = format!(
+            "Editing {}, brush: {brush} (1 solid, 2 one-way, 3 enemy spawn)",
+            levels.current().name
+        );
+        text.sections[2].value = format!("\n{}", editor.status);
+    }
+}
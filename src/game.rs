@@ -1,5 +1,30 @@
+mod attack;
+mod balance;
+mod camera;
+mod collision;
+mod combo;
 mod enemy;
+#[allow(clippy::module_inception)]
 mod game;
+mod generator;
+mod health;
+mod hud;
+mod level;
 mod player;
+mod rng;
+mod schedule;
+mod tower;
+mod upgrade;
+mod wave;
 
-pub use game::MainGamePlugin;
+pub use balance::{Balance, BalanceLoader};
+pub use camera::{clamp_to_level, GameCamera};
+pub use game::{Core, EnergyPoint, MainGamePlugin, SurvivalTime};
+pub use generator::{check_reachable, procedural_level, Surface};
+pub use hud::HudPlugin;
+pub(crate) use level::shipped_levels;
+pub use level::{Level, LevelLoader, Levels, Tile, TileRect};
+pub use player::{JumpArc, Player};
+pub use rng::{GameRng, GameSeed};
+pub use upgrade::{BuyUpgrade, Upgrades};
+pub use wave::{Wave, WaveCleared};
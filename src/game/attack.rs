@@ -0,0 +1,175 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
+
+use super::balance::Balance;
+use super::combo::Combo;
+use super::enemy::Enemy;
+use super::game::hit_enemy;
+use super::health::Respawning;
+use super::player::{Player, CAPSULE_RADIUS};
+use super::rng::GameRng;
+use super::schedule::FIXED_TIMESTEP;
+use crate::actions::Actions;
+use crate::audio::SoundEffect;
+
+/// The player's melee attack, part of the [Balance] file. Durations are in seconds.
+#[derive(Clone, Debug, Deserialize)]
+pub struct AttackConfig {
+    /// Wind-up before the hitbox appears
+    pub startup: f32,
+    /// How long the hitbox stays out
+    pub active: f32,
+    /// Time after the hitbox is gone during which the player cannot attack again
+    pub recovery: f32,
+    /// Shortest time between the start of two attacks
+    pub cooldown: f32,
+    /// Size of the hitbox in front of the player, in pixels
+    pub reach: f32,
+    pub height: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum AttackPhase {
+    Ready,
+    Startup,
+    Active,
+    Recovery,
+}
+
+/// Attack state of the player, it sits next to [Player]
+#[derive(Component)]
+pub struct PlayerAttack {
+    phase: AttackPhase,
+    /// Time left in the current phase
+    phase_timer: f32,
+    /// Time left before the next attack can start
+    cooldown_timer: f32,
+    /// 1 when facing right, -1 when facing left
+    facing: f32,
+    hitbox: Option<Entity>,
+    /// Enemies already damaged by the current swing, so it hits each one once
+    hit: Vec<Entity>,
+}
+
+impl Default for PlayerAttack {
+    fn default() -> Self {
+        Self {
+            phase: AttackPhase::Ready,
+            phase_timer: 0.,
+            cooldown_timer: 0.,
+            facing: 1.,
+            hitbox: None,
+            hit: Vec::new(),
+        }
+    }
+}
+
+fn hitbox_offset(config: &AttackConfig, facing: f32) -> Vec2 {
+    Vec2::new(facing * (CAPSULE_RADIUS + config.reach / 2.), 0.)
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn player_attack(
+    mut commands: Commands,
+    mut actions: ResMut<Actions>,
+    balance: Res<Balance>,
+    rapier_context: Res<RapierContext>,
+    mut players: Query<
+        (Entity, &Transform, &mut PlayerAttack),
+        (With<Player>, Without<Respawning>),
+    >,
+    mut enemies: Query<(Entity, &Transform, &mut Enemy), Without<Player>>,
+    mut sound_effects: EventWriter<SoundEffect>,
+    mut rng: ResMut<GameRng>,
+    mut combo: ResMut<Combo>,
+) {
+    let config = &balance.attack;
+    let attack_pressed = actions.player_attack.take() == Some(true);
+    for (entity, transform, mut attack) in players.iter_mut() {
+        attack.cooldown_timer = (attack.cooldown_timer - FIXED_TIMESTEP).max(0.);
+        attack.phase_timer -= FIXED_TIMESTEP;
+
+        match attack.phase {
+            AttackPhase::Ready => {
+                if let Some(movement) = actions.player_movement {
+                    if movement.x != 0. {
+                        attack.facing = movement.x.signum();
+                    }
+                }
+                if attack_pressed && attack.cooldown_timer <= 0. {
+                    attack.phase = AttackPhase::Startup;
+                    attack.phase_timer = config.startup;
+                    attack.cooldown_timer = config.cooldown;
+                    attack.hit.clear();
+                    sound_effects.send(SoundEffect::Attack);
+                }
+            }
+            AttackPhase::Startup => {
+                if attack.phase_timer <= 0. {
+                    attack.phase = AttackPhase::Active;
+                    attack.phase_timer = config.active;
+                    let shape = shapes::Rectangle {
+                        extents: Vec2::new(config.reach, config.height),
+                        origin: default(),
+                    };
+                    let hitbox = commands
+                        .spawn_bundle(GeometryBuilder::build_as(
+                            &shape,
+                            DrawMode::Fill(bevy_prototype_lyon::prelude::FillMode::color(
+                                Color::rgba(1.0, 1.0, 1.0, 0.5),
+                            )),
+                            Transform::from_translation(
+                                hitbox_offset(config, attack.facing).extend(1.),
+                            ),
+                        ))
+                        .id();
+                    commands.entity(entity).add_child(hitbox);
+                    attack.hitbox = Some(hitbox);
+                }
+            }
+            AttackPhase::Active => {
+                if attack.phase_timer <= 0. {
+                    attack.phase = AttackPhase::Recovery;
+                    attack.phase_timer = config.recovery;
+                    if let Some(hitbox) = attack.hitbox.take() {
+                        commands.entity(hitbox).despawn_recursive();
+                    }
+                }
+            }
+            AttackPhase::Recovery => {
+                if attack.phase_timer <= 0. {
+                    attack.phase = AttackPhase::Ready;
+                }
+            }
+        }
+
+        if attack.phase != AttackPhase::Active {
+            continue;
+        }
+        let center = transform.translation.truncate() + hitbox_offset(config, attack.facing);
+        let shape = Collider::cuboid(config.reach / 2., config.height / 2.);
+        let mut overlapping = Vec::new();
+        rapier_context.intersections_with_shape(
+            center,
+            0.,
+            &shape,
+            QueryFilter::default().exclude_rigid_body(entity),
+            |other| {
+                overlapping.push(other);
+                true
+            },
+        );
+        for other in overlapping {
+            if attack.hit.contains(&other) {
+                continue;
+            }
+            if let Ok(enemy) = enemies.get_mut(other) {
+                attack.hit.push(other);
+                // The swing already played the sound
+                hit_enemy(&mut commands, &balance, &mut rng, &mut combo, enemy);
+            }
+        }
+    }
+}
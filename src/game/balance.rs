@@ -0,0 +1,387 @@
+use bevy::asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+use super::attack::AttackConfig;
+use super::combo::ComboBalance;
+use super::enemy::{Archetype, EnemyKind};
+use super::health::PlayerHealthConfig;
+use super::player::PlayerMovementConfig;
+use super::upgrade::UpgradeDefinition;
+
+/// Everything a designer tunes without recompiling, loaded from
+/// `assets/balance/default.balance.ron` into a resource of the same type.
+#[derive(Clone, Debug, Deserialize, TypeUuid)]
+#[uuid = "5b1f6ea0-6c3a-4a3e-9d6b-2d1f0e9c7a41"]
+pub struct Balance {
+    pub core: CoreBalance,
+    pub tower: TowerBalance,
+    pub player: PlayerMovementConfig,
+    pub attack: AttackConfig,
+    pub health: PlayerHealthConfig,
+    /// Seconds before the first wave
+    pub first_rest: f32,
+    /// Seconds between two waves
+    pub rest: f32,
+    pub waves: Vec<WaveDefinition>,
+    /// How every kind of enemy looks and behaves, each kind must be listed
+    pub enemies: BTreeMap<EnemyKind, Archetype>,
+    /// How waves keep growing once `waves` is exhausted
+    pub endless: EndlessWaves,
+    pub combo: ComboBalance,
+    /// Sold in the shop opened after every cleared wave
+    pub upgrades: Vec<UpgradeDefinition>,
+}
+
+/// Most enemies a single one can split into
+const MAX_SPLIT: u32 = 8;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct CoreBalance {
+    pub hp: i32,
+    pub collect_radius: f32,
+    /// HP lost every time an enemy reaches the core
+    pub damage_per_enemy: i32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TowerBalance {
+    /// Energy spent to build one tower
+    pub cost: i32,
+    pub range: f32,
+    /// Seconds between two shots
+    pub fire_interval: f32,
+    pub projectile_speed: f32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct WaveDefinition {
+    pub count: u32,
+    /// Seconds between two spawns
+    pub interval: f32,
+    pub enemies: Vec<EnemyMix>,
+}
+
+/// One kind of enemy in a wave, picked with a probability proportional to `weight`
+#[derive(Clone, Debug, Deserialize)]
+pub struct EnemyMix {
+    pub kind: EnemyKind,
+    pub weight: u32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct EndlessWaves {
+    /// Enemies added to every wave past the last defined one
+    pub extra_enemies: u32,
+    /// Multiplies the spawn interval of every wave past the last defined one
+    pub interval_factor: f32,
+    pub min_interval: f32,
+}
+
+impl Balance {
+    /// Parses and validates a balance file
+    pub fn from_ron(text: &[u8], path: &str) -> Result<Balance, String> {
+        let balance: Balance =
+            ron::de::from_bytes(text).map_err(|err| format!("could not parse {path}: {err}"))?;
+        balance.validate().map_err(|errors| {
+            format!("invalid balance in {path}:\n  - {}", errors.join("\n  - "))
+        })?;
+        Ok(balance)
+    }
+
+    /// The definition of the wave `number`, starting at 1
+    pub fn wave(&self, number: u32) -> WaveDefinition {
+        let index = number.saturating_sub(1) as usize;
+        if let Some(wave) = self.waves.get(index) {
+            return wave.clone();
+        }
+        let last = self.waves.last().expect("balance without waves");
+        let extra = (index + 1 - self.waves.len()) as u32;
+        WaveDefinition {
+            count: last.count + self.endless.extra_enemies * extra,
+            interval: (last.interval * self.endless.interval_factor.powi(extra as i32))
+                .max(self.endless.min_interval),
+            enemies: last.enemies.clone(),
+        }
+    }
+
+    /// Lists every problem of the file instead of stopping at the first one
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        if self.core.hp <= 0 {
+            errors.push(format!("core.hp must be positive, got {}", self.core.hp));
+        }
+        if self.core.collect_radius <= 0. {
+            errors.push(format!(
+                "core.collect_radius must be positive, got {}",
+                self.core.collect_radius
+            ));
+        }
+        if self.core.damage_per_enemy < 0 {
+            errors.push(format!(
+                "core.damage_per_enemy must not be negative, got {}",
+                self.core.damage_per_enemy
+            ));
+        }
+        if self.tower.cost < 0 {
+            errors.push(format!(
+                "tower.cost must not be negative, got {}",
+                self.tower.cost
+            ));
+        }
+        if self.tower.range <= 0.
+            || self.tower.fire_interval <= 0.
+            || self.tower.projectile_speed <= 0.
+        {
+            errors.push(
+                "tower.range, tower.fire_interval and tower.projectile_speed must be positive"
+                    .to_string(),
+            );
+        }
+        let player = &self.player;
+        if player.max_speed <= 0.
+            || player.acceleration <= 0.
+            || player.deceleration <= 0.
+            || player.jump_impulse <= 0.
+            || player.terminal_velocity <= 0.
+        {
+            errors.push(
+                "player.max_speed, player.acceleration, player.deceleration, player.jump_impulse \
+                 and player.terminal_velocity must be positive"
+                    .to_string(),
+            );
+        }
+        if !(0. ..=1.).contains(&player.air_control) || !(0. ..=1.).contains(&player.jump_cut) {
+            errors
+                .push("player.air_control and player.jump_cut must be between 0 and 1".to_string());
+        }
+        if player.gravity_scale < 0. || player.fall_gravity_multiplier < 0. {
+            errors.push(
+                "player.gravity_scale and player.fall_gravity_multiplier must not be negative"
+                    .to_string(),
+            );
+        }
+        let attack = &self.attack;
+        if attack.startup < 0. || attack.recovery < 0. || attack.cooldown < 0. {
+            errors.push(
+                "attack.startup, attack.recovery and attack.cooldown must not be negative"
+                    .to_string(),
+            );
+        }
+        if attack.active <= 0. || attack.reach <= 0. || attack.height <= 0. {
+            errors
+                .push("attack.active, attack.reach and attack.height must be positive".to_string());
+        }
+        let health = &self.health;
+        if health.max_hp <= 0 {
+            errors.push(format!(
+                "health.max_hp must be positive, got {}",
+                health.max_hp
+            ));
+        }
+        if health.contact_damage < 0 || health.respawn_energy_loss < 0 {
+            errors.push(
+                "health.contact_damage and health.respawn_energy_loss must not be negative"
+                    .to_string(),
+            );
+        }
+        if health.invulnerability < 0. || health.respawn_delay < 0. {
+            errors.push(
+                "health.invulnerability and health.respawn_delay must not be negative".to_string(),
+            );
+        }
+        if self.first_rest < 0. || self.rest < 0. {
+            errors.push("first_rest and rest must not be negative".to_string());
+        }
+        if self.waves.is_empty() {
+            errors.push("waves must contain at least one wave".to_string());
+        }
+        for (i, wave) in self.waves.iter().enumerate() {
+            if wave.count == 0 {
+                errors.push(format!("waves[{i}].count must be positive"));
+            }
+            if wave.interval <= 0. {
+                errors.push(format!(
+                    "waves[{i}].interval must be positive, got {}",
+                    wave.interval
+                ));
+            }
+            if wave.enemies.iter().map(|mix| mix.weight).sum::<u32>() == 0 {
+                errors.push(format!(
+                    "waves[{i}].enemies must contain an enemy with a positive weight"
+                ));
+            }
+        }
+        for kind in EnemyKind::ALL {
+            let enemy = match self.enemies.get(&kind) {
+                Some(enemy) => enemy,
+                None => {
+                    errors.push(format!("enemies must define {kind:?}"));
+                    continue;
+                }
+            };
+            if enemy.hp <= 0 || enemy.speed <= 0. || enemy.radius <= 0. {
+                errors.push(format!(
+                    "enemies[{kind:?}]: hp, speed and radius must be positive"
+                ));
+            }
+            if enemy.sides < 3 {
+                errors.push(format!(
+                    "enemies[{kind:?}].sides must be at least 3, got {}",
+                    enemy.sides
+                ));
+            }
+            if enemy.drops.iter().map(|drop| drop.1).sum::<u32>() == 0 {
+                errors.push(format!(
+                    "enemies[{kind:?}].drops must contain a count with a positive weight"
+                ));
+            }
+            if let Some((_, count)) = enemy.split {
+                if !(1..=MAX_SPLIT).contains(&count) {
+                    errors.push(format!(
+                        "enemies[{kind:?}].split must spawn between 1 and {MAX_SPLIT} enemies, \
+                         got {count}"
+                    ));
+                }
+            }
+            // A kind coming back along its chain of splits would keep splitting forever
+            let mut chain = vec![kind];
+            let mut next = enemy.split.map(|split| split.0);
+            while let Some(split) = next {
+                if chain.contains(&split) {
+                    errors.push(format!(
+                        "enemies[{kind:?}].split ends up splitting into itself"
+                    ));
+                    break;
+                }
+                chain.push(split);
+                next = self
+                    .enemies
+                    .get(&split)
+                    .and_then(|enemy| enemy.split.map(|split| split.0));
+            }
+            if let Some(explosion) = &enemy.explosion {
+                if explosion.radius <= 0. || explosion.damage < 0 {
+                    errors.push(format!(
+                        "enemies[{kind:?}].explosion: radius must be positive and damage \
+                         not negative"
+                    ));
+                }
+            }
+        }
+        if self.endless.interval_factor <= 0. || self.endless.min_interval <= 0. {
+            errors.push(
+                "endless.interval_factor and endless.min_interval must be positive".to_string(),
+            );
+        }
+        if self.combo.window <= 0.
+            || self.combo.kills_per_level == 0
+            || self.combo.max_multiplier < 1
+        {
+            errors.push(
+                "combo.window and combo.kills_per_level must be positive, \
+                 combo.max_multiplier at least 1"
+                    .to_string(),
+            );
+        }
+        for (i, upgrade) in self.upgrades.iter().enumerate() {
+            if upgrade.amount <= 0. || upgrade.base_cost < 0 || upgrade.cost_growth < 1. {
+                errors.push(format!(
+                    "upgrades[{i}]: amount must be positive, base_cost not negative \
+                     and cost_growth at least 1"
+                ));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl Default for Balance {
+    /// The shipped balance file, embedded for runs without an asset server
+    fn default() -> Self {
+        ron::from_str(include_str!("../../assets/balance/default.balance.ron"))
+            .expect("the default balance file is invalid")
+    }
+}
+
+#[derive(Default)]
+pub struct BalanceLoader;
+
+impl AssetLoader for BalanceLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let path = load_context.path().display().to_string();
+            // A failed asset would keep the game on the loading screen forever
+            let balance = Balance::from_ron(bytes, &path).unwrap_or_else(|message| {
+                warn!("{message}\nfalling back to the balance shipped with the game");
+                Balance::default()
+            });
+            load_context.set_default_asset(LoadedAsset::new(balance));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["balance.ron"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_balance_is_valid() {
+        assert_eq!(Balance::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn rejects_an_invalid_file() {
+        let error = Balance::from_ron(b"(core: (hp: -5))", "balance/broken.balance.ron")
+            .expect_err("the file should be rejected");
+        assert!(error.contains("balance/broken.balance.ron"), "{error}");
+    }
+
+    #[test]
+    fn reports_a_missing_enemy_kind() {
+        let mut balance = Balance::default();
+        balance.enemies.remove(&EnemyKind::Tank);
+        let errors = balance.validate().unwrap_err();
+        assert_eq!(errors, vec!["enemies must define Tank".to_string()]);
+    }
+
+    #[test]
+    fn reports_broken_enemy_stats() {
+        let mut balance = Balance::default();
+        let walker = balance.enemies.get_mut(&EnemyKind::Walker).unwrap();
+        walker.hp = 0;
+        walker.speed = -1.;
+        let splitter = balance.enemies.get_mut(&EnemyKind::Splitter).unwrap();
+        splitter.split = Some((EnemyKind::Walker, MAX_SPLIT + 1));
+        let errors = balance.validate().unwrap_err();
+        assert_eq!(errors.len(), 2, "{errors:?}");
+        assert!(errors[0].starts_with("enemies[Walker]"));
+        assert!(errors[1].starts_with("enemies[Splitter].split"));
+    }
+
+    #[test]
+    fn reports_enemies_splitting_forever() {
+        let mut balance = Balance::default();
+        let walker = balance.enemies.get_mut(&EnemyKind::Walker).unwrap();
+        walker.split = Some((EnemyKind::Splitter, 1));
+        let errors = balance.validate().unwrap_err();
+        assert!(errors
+            .iter()
+            .any(|error| error == "enemies[Splitter].split ends up splitting into itself"));
+    }
+}
@@ -0,0 +1,135 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use super::health::Respawning;
+use super::level::{Level, Levels};
+use super::player::Player;
+use crate::display::ViewArea;
+
+/// Half size of the box around the camera's focus the player moves in freely, in pixels
+const DEAD_ZONE: Vec2 = Vec2::new(60., 40.);
+/// How far ahead of the player the camera looks, towards where it runs, in pixels
+const LOOK_AHEAD: f32 = 80.;
+/// Horizontal speed above which the player counts as running somewhere, in pixels per second
+const LOOK_AHEAD_SPEED: f32 = 20.;
+/// How quickly the camera catches up with where it should look, per second
+const FOLLOW_RATE: f32 = 6.;
+
+/// The game camera. During a run it follows the player, without ever showing what lies
+/// beyond the level.
+#[derive(Component, Default)]
+pub struct GameCamera {
+    /// Point kept within [DEAD_ZONE] of the player
+    focus: Vec2,
+    /// Current horizontal offset towards where the player runs, eases to ±[LOOK_AHEAD]
+    look_ahead: f32,
+}
+
+/// The closest camera position to `center` that keeps the `visible` part of the world,
+/// see [ViewArea::visible], inside the level.
+/// On an axis where the level is smaller than the view, the level is centered instead.
+pub fn clamp_to_level(center: Vec2, visible: Vec2, level: &Level) -> Vec2 {
+    let room = ((level.size() - visible) / 2.).max(Vec2::ZERO);
+    center.clamp(-room, room)
+}
+
+/// Starts a run looking at the player start
+pub fn center_camera(
+    levels: Res<Levels>,
+    view: Res<ViewArea>,
+    mut camera: Query<(&mut Transform, &mut GameCamera)>,
+) {
+    let level = levels.current();
+    for (mut transform, mut camera) in camera.iter_mut() {
+        camera.focus = level.player_start();
+        camera.look_ahead = 0.;
+        let center = clamp_to_level(camera.focus, view.visible, level);
+        transform.translation = center.extend(transform.translation.z);
+    }
+}
+
+/// Runs on the rendered, interpolated position of the player, so the view does not stutter.
+/// Does nothing while there is no player, out of runs or while it respawns.
+#[allow(clippy::type_complexity)]
+pub fn follow_player(
+    time: Res<Time>,
+    levels: Res<Levels>,
+    view: Res<ViewArea>,
+    player: Query<(&Transform, &Velocity), (With<Player>, Without<Respawning>)>,
+    mut camera: Query<(&mut Transform, &mut GameCamera), Without<Player>>,
+) {
+    let (player, velocity) = match player.get_single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+    let player = player.translation.truncate();
+    for (mut transform, mut camera) in camera.iter_mut() {
+        camera.focus = camera.focus.clamp(player - DEAD_ZONE, player + DEAD_ZONE);
+        // Keeps looking the same way while the player stands still
+        if velocity.linvel.x.abs() > LOOK_AHEAD_SPEED {
+            let target = LOOK_AHEAD * velocity.linvel.x.signum();
+            let eased = target - camera.look_ahead;
+            camera.look_ahead += eased * ease(time.delta_seconds());
+        }
+
+        let target = clamp_to_level(
+            camera.focus + Vec2::new(camera.look_ahead, 0.),
+            view.visible,
+            levels.current(),
+        );
+        let center = transform.translation.truncate();
+        let center = center + (target - center) * ease(time.delta_seconds());
+        transform.translation = center.extend(transform.translation.z);
+    }
+}
+
+/// Share of the remaining distance covered this frame, independent of the frame rate
+fn ease(delta: f32) -> f32 {
+    1. - (-FOLLOW_RATE * delta).exp()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::{DisplaySettings, ViewScaling};
+
+    /// 32 by 18 tiles of 40 pixels, 1280 by 720 pixels
+    fn level() -> Level {
+        Level {
+            name: "Test".to_string(),
+            tile_size: 40.,
+            rows: vec![".".repeat(32); 18],
+            path: String::new(),
+            seed: None,
+        }
+    }
+
+    #[test]
+    fn letterboxed_wide_window_scrolls_to_the_level_edges() {
+        let settings = DisplaySettings {
+            scaling: ViewScaling::Letterbox,
+            ..default()
+        };
+        let view = ViewArea::fit(&settings, Vec2::new(1600., 600.));
+        assert_eq!(view.visible, settings.virtual_size());
+
+        // The bars hide everything past 400 pixels from the center, so the camera can go
+        // 240 pixels right before showing the right edge of the level
+        let far_right = clamp_to_level(Vec2::new(1000., 0.), view.visible, &level());
+        assert_eq!(far_right, Vec2::new(240., 0.));
+    }
+
+    #[test]
+    fn expanded_wide_window_shows_more_of_the_level() {
+        let settings = DisplaySettings {
+            scaling: ViewScaling::Expand,
+            ..default()
+        };
+        let view = ViewArea::fit(&settings, Vec2::new(1600., 600.));
+        assert_eq!(view.visible, Vec2::new(1600., 600.));
+
+        // Wider than the level, which stays centered
+        let center = clamp_to_level(Vec2::new(1000., 100.), view.visible, &level());
+        assert_eq!(center, Vec2::new(0., 60.));
+    }
+}
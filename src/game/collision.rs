@@ -0,0 +1,89 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use super::enemy::Enemy;
+use super::game::Core;
+use super::player::Player;
+
+/// What a collider taking part in a collision belongs to, as far as the gameplay cares
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Collidable {
+    Player,
+    Enemy,
+    Core,
+}
+
+/// An enemy started touching the core
+pub struct EnemyHitCore {
+    pub enemy: Entity,
+    pub core: Entity,
+}
+
+/// An enemy touches the player, sent every gameplay tick until they part
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct EnemyHitPlayer {
+    pub enemy: Entity,
+    pub player: Entity,
+}
+
+/// Turns Rapier's collisions into the typed events above, so gameplay systems neither read
+/// [CollisionEvent] nor care in which order its two entities come.
+/// Systems consuming those events run after this one, during the same gameplay tick.
+#[allow(clippy::too_many_arguments)]
+pub fn route_collisions(
+    mut collision_events: EventReader<CollisionEvent>,
+    players: Query<(), With<Player>>,
+    enemies: Query<(), With<Enemy>>,
+    cores: Query<(), With<Core>>,
+    rapier_context: Res<RapierContext>,
+    mut touching_player: Local<Vec<EnemyHitPlayer>>,
+    mut enemy_hit_core: EventWriter<EnemyHitCore>,
+    mut enemy_hit_player: EventWriter<EnemyHitPlayer>,
+) {
+    // Enemies leaning on the player keep hurting it, its invulnerability spaces the hits
+    touching_player.retain(|hit| {
+        rapier_context
+            .contact_pair(hit.enemy, hit.player)
+            .is_some_and(|pair| pair.has_any_active_contacts())
+    });
+
+    let classify = |entity: Entity| {
+        if enemies.contains(entity) {
+            Some(Collidable::Enemy)
+        } else if players.contains(entity) {
+            Some(Collidable::Player)
+        } else if cores.contains(entity) {
+            Some(Collidable::Core)
+        } else {
+            None
+        }
+    };
+    for collision_event in collision_events.iter() {
+        let (a, b) = match collision_event {
+            CollisionEvent::Started(a, b, _) => (*a, *b),
+            CollisionEvent::Stopped(..) => continue,
+        };
+        // Sorted so every pair is handled once, whichever entity Rapier put first
+        let ((kind_a, a), (kind_b, b)) = match (classify(a), classify(b)) {
+            (Some(kind_a), Some(kind_b)) if kind_a <= kind_b => ((kind_a, a), (kind_b, b)),
+            (Some(kind_a), Some(kind_b)) => ((kind_b, b), (kind_a, a)),
+            _ => continue,
+        };
+        match (kind_a, kind_b) {
+            (Collidable::Player, Collidable::Enemy) => {
+                let hit = EnemyHitPlayer {
+                    enemy: b,
+                    player: a,
+                };
+                if !touching_player.contains(&hit) {
+                    touching_player.push(hit);
+                }
+            }
+            (Collidable::Enemy, Collidable::Core) => {
+                enemy_hit_core.send(EnemyHitCore { enemy: a, core: b })
+            }
+            _ => {}
+        }
+    }
+    enemy_hit_player.send_batch(touching_player.iter().copied());
+}
@@ -0,0 +1,54 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use super::balance::Balance;
+use super::schedule::FIXED_TIMESTEP;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ComboBalance {
+    /// Seconds a kill keeps the combo going
+    pub window: f32,
+    /// Kills needed to raise the multiplier by one
+    pub kills_per_level: u32,
+    pub max_multiplier: i32,
+}
+
+/// Kills chained with less than [ComboBalance::window] seconds between them.
+/// Energy dropped by a kill is multiplied by [Combo::multiplier] as it stood at that kill.
+pub struct Combo {
+    pub kills: u32,
+    /// Seconds left before the combo drops
+    pub remaining: f32,
+    balance: ComboBalance,
+}
+
+impl FromWorld for Combo {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            kills: 0,
+            remaining: 0.,
+            balance: world.resource::<Balance>().combo.clone(),
+        }
+    }
+}
+
+impl Combo {
+    pub fn register_kill(&mut self) {
+        self.kills += 1;
+        self.remaining = self.balance.window;
+    }
+
+    pub fn multiplier(&self) -> i32 {
+        (1 + (self.kills / self.balance.kills_per_level) as i32).min(self.balance.max_multiplier)
+    }
+}
+
+pub fn tick_combo(mut combo: ResMut<Combo>) {
+    if combo.kills == 0 {
+        return;
+    }
+    combo.remaining -= FIXED_TIMESTEP;
+    if combo.remaining <= 0. {
+        combo.kills = 0;
+    }
+}
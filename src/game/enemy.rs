@@ -1,81 +1,194 @@
 use bevy::prelude::*;
+use bevy::utils::Duration;
 use bevy_prototype_lyon::prelude::*;
 use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
 
+use rand::seq::SliceRandom;
 use rand::Rng;
 
-use crate::constants::*;
+use super::balance::Balance;
+use super::game::EnergyTier;
+use super::level::EnemyZones;
+use super::rng::GameRng;
+use super::schedule::{InterpolatedTransform, FIXED_TIMESTEP};
+use super::wave::{Wave, WavePhase};
+use crate::cleanup::DespawnOnExit;
+use crate::GameState;
 
-#[derive(Component)]
-pub struct Enemy {
-    speed: f32,
+/// The kinds of enemy a wave can be made of, see [EnemyKind::archetype]
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EnemyKind {
+    /// Falls to the ground and walks to the core
+    Walker,
+    /// Small and fast, flies straight at the core
+    Flyer,
+    /// Slow walker taking several hits to kill
+    Tank,
+    /// Walks to the core and breaks into walkers when killed
+    Splitter,
+    /// Flies at the core and explodes next to it
+    Kamikaze,
 }
 
-#[derive(Component)]
-pub struct Target;
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum Movement {
+    /// Pulled down by gravity, only steers horizontally
+    Walk,
+    /// Ignores gravity and heads straight to the target
+    Fly,
+}
 
-impl Default for Enemy {
-    fn default() -> Self {
-        Self { speed: 40.0 }
-    }
+/// Damages the core once the enemy gets within `radius` of it, killing the enemy
+#[derive(Clone, Debug, Deserialize)]
+pub struct Explosion {
+    pub radius: f32,
+    pub damage: i32,
 }
 
-pub struct Timers {
-    enemy_spawn_timer: Timer,
+/// Everything that makes one kind of enemy look and behave differently from the others,
+/// part of the [Balance] file
+#[derive(Clone, Debug, Deserialize)]
+pub struct Archetype {
+    pub hp: i32,
+    /// In pixels per second
+    pub speed: f32,
+    pub sides: usize,
+    pub radius: f32,
+    pub color: Color,
+    pub movement: Movement,
+    /// Number of energy orbs dropped on death, each with the weight it is picked with
+    pub drops: Vec<(u32, u32)>,
+    /// How much every dropped orb is worth
+    pub drop_tier: EnergyTier,
+    /// Enemies spawned in place of this one when it dies
+    #[serde(default)]
+    pub split: Option<(EnemyKind, u32)>,
+    #[serde(default)]
+    pub explosion: Option<Explosion>,
 }
 
-impl Timers {
-    fn new() -> Self {
-        Self {
-            enemy_spawn_timer: Timer::from_seconds(3.0, true),
-        }
+impl EnemyKind {
+    /// Every kind, so the balance file can be checked for each of them
+    pub const ALL: [EnemyKind; 5] = [
+        EnemyKind::Walker,
+        EnemyKind::Flyer,
+        EnemyKind::Tank,
+        EnemyKind::Splitter,
+        EnemyKind::Kamikaze,
+    ];
+
+    pub fn archetype(self, balance: &Balance) -> &Archetype {
+        balance
+            .enemies
+            .get(&self)
+            .expect("validated when the balance was loaded")
     }
 }
 
-impl Default for Timers {
-    fn default() -> Self {
-        Timers::new()
+impl Archetype {
+    fn collider(&self) -> Collider {
+        match self.movement {
+            // Walkers are squares and hexagons, a box keeps them from rolling off their feet
+            Movement::Walk => Collider::cuboid(self.radius, self.radius),
+            Movement::Fly => Collider::ball(self.radius),
+        }
     }
 }
 
+#[derive(Component)]
+pub struct Enemy {
+    pub kind: EnemyKind,
+    pub hp: i32,
+}
+
+#[derive(Component)]
+pub struct Target;
+
 pub fn move_enemies(
+    balance: Res<Balance>,
     target: Query<&Transform, With<Target>>,
     mut query: Query<(&mut Velocity, &Transform, &Enemy)>,
 ) {
+    let target = target.single();
     for (mut velocity, transform, enemy) in query.iter_mut() {
-        let vec = target.single().translation - transform.translation;
-        velocity.linvel = vec.truncate().normalize() * enemy.speed;
+        let archetype = enemy.kind.archetype(&balance);
+        let vec = (target.translation - transform.translation).truncate();
+        velocity.linvel = match archetype.movement {
+            Movement::Walk => Vec2::new(vec.x.signum() * archetype.speed, velocity.linvel.y),
+            Movement::Fly => vec.normalize_or_zero() * archetype.speed,
+        };
     }
 }
 
-pub fn spawn_enemies(mut commands: Commands, time: Res<Time>, mut timers: ResMut<Timers>) {
-    timers.enemy_spawn_timer.tick(time.delta());
+pub fn spawn_enemies(
+    mut commands: Commands,
+    mut wave: ResMut<Wave>,
+    mut rng: ResMut<GameRng>,
+    zones: Res<EnemyZones>,
+    balance: Res<Balance>,
+) {
+    if wave.phase != WavePhase::Spawning {
+        return;
+    }
+    wave.spawn_timer
+        .tick(Duration::from_secs_f32(FIXED_TIMESTEP));
 
-    if timers.enemy_spawn_timer.just_finished() {
-        let radius = 10.;
-        let shape = shapes::RegularPolygon {
-            sides: 5,
-            feature: RegularPolygonFeature::Radius(radius),
-            ..default()
-        };
-        let mut rnd_gen = rand::thread_rng();
+    if wave.spawn_timer.just_finished() {
+        wave.spawned += 1;
+        let rnd_gen = &mut rng.spawning;
+        let mix = wave
+            .definition
+            .enemies
+            .choose_weighted(rnd_gen, |mix| mix.weight)
+            .expect("validated when the balance was loaded");
+        let zone = zones
+            .0
+            .choose(rnd_gen)
+            .expect("validated when the level was loaded");
         let transform = Transform::from_xyz(
-            rnd_gen.gen_range(0.0..WIN_WIDTH) - (WIN_WIDTH / 2.0),
-            rnd_gen.gen_range(0.0..100.),
+            rnd_gen.gen_range(zone.min.x..zone.max.x),
+            rnd_gen.gen_range(zone.min.y..zone.max.y),
             0.0,
         );
-        commands
-            .spawn()
-            .insert(Enemy::default())
-            .insert(RigidBody::Dynamic)
-            .insert(Velocity::zero())
-            .insert_bundle(TransformBundle::from(transform))
-            .insert(ActiveEvents::COLLISION_EVENTS)
-            .insert_bundle(GeometryBuilder::build_as(
-                &shape,
-                DrawMode::Fill(bevy_prototype_lyon::prelude::FillMode::color(Color::RED)),
-                transform,
-            ))
-            .insert(Collider::ball(radius));
+        spawn_enemy(&mut commands, &balance, mix.kind, transform);
     }
 }
+
+pub fn spawn_enemy(
+    commands: &mut Commands,
+    balance: &Balance,
+    kind: EnemyKind,
+    transform: Transform,
+) {
+    let archetype = kind.archetype(balance);
+    let shape = shapes::RegularPolygon {
+        sides: archetype.sides,
+        feature: RegularPolygonFeature::Radius(archetype.radius),
+        ..default()
+    };
+    let mut enemy = commands.spawn();
+    enemy
+        .insert(DespawnOnExit(GameState::Playing))
+        .insert(Enemy {
+            kind,
+            hp: archetype.hp,
+        })
+        .insert(RigidBody::Dynamic)
+        .insert(InterpolatedTransform::default())
+        .insert(Velocity::zero())
+        .insert_bundle(TransformBundle::from(transform))
+        .insert(ActiveEvents::COLLISION_EVENTS)
+        .insert_bundle(GeometryBuilder::build_as(
+            &shape,
+            DrawMode::Fill(bevy_prototype_lyon::prelude::FillMode::color(
+                archetype.color,
+            )),
+            transform,
+        ))
+        .insert(archetype.collider());
+    match archetype.movement {
+        Movement::Walk => enemy.insert(LockedAxes::ROTATION_LOCKED),
+        Movement::Fly => enemy.insert(GravityScale(0.)),
+    };
+}
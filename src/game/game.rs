@@ -1,153 +1,163 @@
+use super::attack::player_attack;
+use super::balance::Balance;
+use super::camera::{center_camera, follow_player};
+use super::collision::{route_collisions, EnemyHitCore, EnemyHitPlayer};
+use super::combo::{tick_combo, Combo};
 use super::enemy::*;
+use super::generator::generate_level;
+use super::health::{damage_player, respawn_player};
+use super::level::{setup_level, Levels};
 use super::player::*;
-use crate::constants::{WIN_HEIGHT, WIN_WIDTH};
-use crate::loading::AudioAssets;
-use crate::loading::FontAssets;
-use crate::GameState;
+use super::rng::*;
+use super::schedule::*;
+use super::tower::*;
+use super::upgrade::{buy_upgrades, regenerate_core, BuyUpgrade, Upgrades};
+use super::wave::*;
+use crate::actions::Actions;
+use crate::audio::SoundEffect;
+use crate::cleanup::{DespawnOnExit, ResetOnEnterAppExt};
+use crate::{GameState, RunTransition};
 
 use bevy::prelude::*;
-use bevy_kira_audio::Audio;
+use bevy::transform::TransformSystem;
 use bevy_prototype_lyon::entity::ShapeBundle;
 use bevy_prototype_lyon::prelude::*;
 use bevy_rapier2d::prelude::*;
+use rand::seq::SliceRandom;
 use rand::Rng;
+use serde::Deserialize;
 
 pub struct MainGamePlugin;
 
 impl Plugin for MainGamePlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugin(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0))
-            .add_plugin(ShapePlugin)
-            .init_resource::<Timers>()
+        // A seed inserted beforehand replays the same run every time, see [GameSeed]
+        if !app.world.contains_resource::<GameSeed>() {
+            app.add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(reroll_seed.exclusive_system().at_start()),
+            );
+        }
+        app.add_plugin(FixedStepPlugin)
+            .add_event::<SoundEffect>()
+            .init_resource::<GameSeed>()
+            .init_resource::<Balance>()
+            .init_resource::<Levels>()
+            .add_event::<WaveCleared>()
+            .add_event::<BuyUpgrade>()
+            .add_event::<EnemyHitCore>()
+            .add_event::<EnemyHitPlayer>()
+            .reset_on_enter::<GameRng>(GameState::Playing)
+            .reset_on_enter::<EnergyPoint>(GameState::Playing)
+            .reset_on_enter::<SurvivalTime>(GameState::Playing)
+            .reset_on_enter::<Wave>(GameState::Playing)
+            .reset_on_enter::<Actions>(GameState::Playing)
+            .reset_on_enter::<Upgrades>(GameState::Playing)
+            .reset_on_enter::<Combo>(GameState::Playing)
+            // Upgrades are bought from the shop, while the run is paused
+            .add_system(buy_upgrades)
             .add_system_set(
                 SystemSet::on_enter(GameState::Playing)
                     .with_system(setup_graphics)
-                    .with_system(setup_ground)
-                    .with_system(setup_core)
-                    .with_system(setup_player)
-                    .with_system(setup_hp)
-                    .with_system(setup_score),
+                    .with_system(generate_level)
+                    .with_system(setup_level.after(generate_level))
+                    .with_system(setup_core.after(generate_level))
+                    .with_system(setup_player.after(generate_level))
+                    .with_system(center_camera.after(generate_level))
+                    .with_system(resume_physics),
             )
-            .add_system_set(
+            .add_fixed_system_set(
                 SystemSet::on_update(GameState::Playing)
                     // .with_system(print_ball_altitude)
+                    .with_system(tick_survival_time)
                     .with_system(move_player_system)
                     .with_system(spawn_enemies)
+                    .with_system(update_wave)
                     .with_system(move_enemies)
-                    .with_system(despawn_enemies)
+                    .with_system(player_attack)
+                    .with_system(route_collisions)
+                    .with_system(damage_player.after(route_collisions))
+                    .with_system(respawn_player)
+                    .with_system(explode_enemies)
+                    .with_system(build_tower)
+                    .with_system(fire_towers)
+                    .with_system(move_projectiles)
                     .with_system(move_energy)
                     .with_system(start_collect)
                     .with_system(collect_energy)
-                    .with_system(atack_core)
-                    .with_system(update_hp_text)
-                    .with_system(update_score)
-                    .with_system(check_gameover),
-            );
-
-        #[cfg(debug_assertions)]
-        {
-            app.add_plugin(RapierDebugRenderPlugin::default());
-        }
+                    .with_system(atack_core.after(route_collisions))
+                    .with_system(regenerate_core)
+                    .with_system(tick_combo),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(log_wave_cleared)
+                    .with_system(check_gameover.label(RunTransition::GameOver)),
+            )
+            // Outside of the gameplay stage, states cannot drive systems there
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                follow_player
+                    .after(interpolate_transforms)
+                    .before(TransformSystem::TransformPropagate),
+            )
+            .add_system_set(SystemSet::on_pause(GameState::Playing).with_system(freeze_physics))
+            .add_system_set(SystemSet::on_resume(GameState::Playing).with_system(resume_physics));
     }
 }
 
-#[derive(Component)]
-struct EnergyText;
-
-struct EnergyPoint(i32);
+/// Picks the seed of the run about to start. The random streams are drawn from it here too,
+/// whether their own reset ran before this one or not.
+fn reroll_seed(world: &mut World) {
+    world.insert_resource(GameSeed::default());
+    let rng = GameRng::from_world(world);
+    world.insert_resource(rng);
+}
 
-fn setup_score(mut commands: Commands, font_assets: Res<FontAssets>) {
-    commands
-        .spawn()
-        .insert_bundle(
-            TextBundle::from_sections([
-                TextSection::new(
-                    "Energy: ",
-                    TextStyle {
-                        font: font_assets.fira_sans.clone(),
-                        font_size: 60.0,
-                        color: Color::WHITE,
-                    },
-                ),
-                TextSection::from_style(TextStyle {
-                    font: font_assets.fira_sans.clone(),
-                    font_size: 60.0,
-                    color: Color::GOLD,
-                }),
-            ])
-            .with_style(Style {
-                align_self: AlignSelf::FlexEnd,
-                ..default()
-            }),
-        )
-        .insert(EnergyText);
-    commands.insert_resource(EnergyPoint(0));
+/// Keeps the run still while another state is pushed on top of it
+fn freeze_physics(mut config: ResMut<RapierConfiguration>) {
+    config.physics_pipeline_active = false;
 }
 
-fn update_score(mut query: Query<&mut Text, With<EnergyText>>, score: Res<EnergyPoint>) {
-    let s = score.0;
-    for mut text in &mut query {
-        text.sections[1].value = format!("{s:.2}");
-    }
+fn resume_physics(mut config: ResMut<RapierConfiguration>) {
+    config.physics_pipeline_active = true;
 }
 
-#[derive(Component)]
-struct HpText;
+#[derive(Default)]
+pub struct EnergyPoint(pub i32);
 
-fn setup_hp(mut commands: Commands, font_assets: Res<FontAssets>) {
-    commands
-        .spawn()
-        .insert_bundle(
-            TextBundle::from_sections([
-                TextSection::new(
-                    "HP: ",
-                    TextStyle {
-                        font: font_assets.fira_sans.clone(),
-                        font_size: 60.0,
-                        color: Color::WHITE,
-                    },
-                ),
-                TextSection::from_style(TextStyle {
-                    font: font_assets.fira_sans.clone(),
-                    font_size: 60.0,
-                    color: Color::GOLD,
-                }),
-            ])
-            .with_style(Style {
-                align_self: AlignSelf::FlexEnd,
-                ..default()
-            }),
-        )
-        .insert(HpText);
-}
+/// Seconds of gameplay since the run started
+#[derive(Default)]
+pub struct SurvivalTime(pub f32);
 
-fn update_hp_text(mut query: Query<&mut Text, With<HpText>>, core: Query<&Core>) {
-    let hp = core.single().hp;
-    for mut text in &mut query {
-        text.sections[1].value = format!("{hp:.2}");
-    }
+fn tick_survival_time(mut survival_time: ResMut<SurvivalTime>) {
+    survival_time.0 += FIXED_TIMESTEP;
 }
 
 #[derive(Component)]
-struct Core {
-    hp: i32,
+pub struct Core {
+    pub hp: i32,
+    pub max_hp: i32,
+    /// HP regained per second
+    pub regen: f32,
+    /// Regenerated HP not yet added to `hp`
+    pub regen_progress: f32,
+    /// Enemies this close lose one HP every time the core is hit, 0 without the upgrade
+    pub reflect_radius: f32,
 }
 
-impl Default for Core {
-    fn default() -> Self {
-        Self { hp: 100 }
-    }
-}
 #[derive(Component)]
-struct CollectArea {
-    radius: f32,
+pub struct CollectArea {
+    pub radius: f32,
+    /// Divides the time collected energy takes to reach the core
+    pub homing_speed: f32,
 }
 
-fn setup_graphics(mut commands: Commands) {
+fn setup_graphics(_commands: Commands) {
     // commands.spawn_bundle(Camera2dBundle::default());
 }
 
-fn setup_core(mut commands: Commands) {
+fn setup_core(mut commands: Commands, balance: Res<Balance>, levels: Res<Levels>) {
     let radius: f32 = 20.;
     let shape = shapes::Circle {
         radius,
@@ -155,7 +165,14 @@ fn setup_core(mut commands: Commands) {
     };
     commands
         .spawn()
-        .insert(Core::default())
+        .insert(DespawnOnExit(GameState::Playing))
+        .insert(Core {
+            hp: balance.core.hp,
+            max_hp: balance.core.hp,
+            regen: 0.,
+            regen_progress: 0.,
+            reflect_radius: 0.,
+        })
         .insert(Target)
         .insert_bundle(GeometryBuilder::build_as(
             &shape,
@@ -164,14 +181,13 @@ fn setup_core(mut commands: Commands) {
         ))
         .insert(RigidBody::Dynamic)
         .insert(Collider::ball(radius))
+        .insert(InterpolatedTransform::default())
         .insert(Damping {
             linear_damping: 0.7,
             ..Default::default()
         })
-        .insert_bundle(TransformBundle::from(Transform::from_xyz(
-            0.0,
-            -(WIN_HEIGHT / 2.) + 40. + radius,
-            0.0,
+        .insert_bundle(TransformBundle::from(Transform::from_translation(
+            (levels.current().core_position() + Vec2::new(0., radius)).extend(0.),
         )))
         .with_children(|parent| {
             parent
@@ -179,116 +195,216 @@ fn setup_core(mut commands: Commands) {
                 .insert_bundle(TransformBundle::from_transform(Transform::from_xyz(
                     0., 0., 0.,
                 )))
-                .insert(CollectArea { radius: 200.0 })
-                .insert(Collider::ball(200.0))
+                .insert(CollectArea {
+                    radius: balance.core.collect_radius,
+                    homing_speed: 1.,
+                })
+                .insert(Collider::ball(balance.core.collect_radius))
                 .insert(Sensor)
                 .insert(ColliderMassProperties::Density(0.0));
         });
 }
 
-fn setup_ground(mut commands: Commands) {
-    let plain = shapes::Rectangle {
-        extents: Vec2::new(WIN_WIDTH, 40.),
-        ..default()
-    };
-    commands
-        .spawn()
-        .insert_bundle(GeometryBuilder::build_as(
-            &plain,
-            DrawMode::Fill(bevy_prototype_lyon::prelude::FillMode::color(Color::BLACK)),
-            default(),
-        ))
-        .insert(Collider::cuboid(WIN_WIDTH, 20.0))
-        .insert_bundle(TransformBundle::from(Transform::from_xyz(
-            0.0,
-            -WIN_HEIGHT / 2. + 20.,
-            0.0,
-        )));
-}
-
+#[allow(clippy::too_many_arguments)]
 fn atack_core(
     mut commands: Commands,
-    mut collision_events: EventReader<CollisionEvent>,
-    mut core: Query<(Entity, &mut Core)>,
-    enemies: Query<(Entity, &Transform), With<Enemy>>,
-    audio_assets: Res<AudioAssets>,
-    audio: Res<Audio>,
+    mut hit_events: EventReader<EnemyHitCore>,
+    mut core: Query<(&Transform, &mut Core)>,
+    mut enemies: Query<(Entity, &Transform, &mut Enemy), Without<Core>>,
+    balance: Res<Balance>,
+    mut sound_effects: EventWriter<SoundEffect>,
+    mut rng: ResMut<GameRng>,
+    mut combo: ResMut<Combo>,
 ) {
-    for collision_event in collision_events.iter() {
-        match collision_event {
-            CollisionEvent::Started(a, b, _) => {
-                if core.single().0 == *a {
-                    if let Some(enemy) = enemies.iter().find(|x| x.0 == *b) {
-                        commands.entity(enemy.0).despawn();
-                        core.single_mut().1.hp -= 10;
-                    }
-                }
-                if core.single().0 == *b {
-                    if let Some(enemy) = enemies.iter().find(|x| x.0 == *a) {
-                        commands.entity(enemy.0).despawn();
-                        core.single_mut().1.hp -= 10;
-                    }
-                }
+    for event in hit_events.iter() {
+        let (core_transform, mut core) = match core.get_mut(event.core) {
+            Ok(core) => core,
+            Err(_) => continue,
+        };
+        if let Ok((entity, _, mut enemy)) = enemies.get_mut(event.enemy) {
+            if enemy.hp <= 0 {
+                continue;
             }
-            _ => {}
+            // Spent on the core, the reflect aura must not kill it a second time
+            enemy.hp = 0;
+            commands.entity(entity).despawn();
+            core.hp -= balance.core.damage_per_enemy;
+            reflect_damage(
+                &mut commands,
+                &balance,
+                &mut sound_effects,
+                &mut rng,
+                &mut combo,
+                core_transform,
+                &core,
+                &mut enemies,
+            );
         }
     }
 }
 
-fn despawn_enemies(
-    mut commands: Commands,
-    mut collision_events: EventReader<CollisionEvent>,
-    mut contact_force_events: EventReader<ContactForceEvent>,
-    player: Query<Entity, With<Player>>,
-    enemies: Query<(Entity, &Transform), With<Enemy>>,
-    audio_assets: Res<AudioAssets>,
-    audio: Res<Audio>,
+/// Hits every enemy inside the core's reflect aura, called whenever the core takes damage
+#[allow(clippy::too_many_arguments)]
+fn reflect_damage(
+    commands: &mut Commands,
+    balance: &Balance,
+    sound_effects: &mut EventWriter<SoundEffect>,
+    rng: &mut GameRng,
+    combo: &mut Combo,
+    core_transform: &Transform,
+    core: &Core,
+    enemies: &mut Query<(Entity, &Transform, &mut Enemy), Without<Core>>,
 ) {
-    for collision_event in collision_events.iter() {
-        match collision_event {
-            CollisionEvent::Started(a, b, _) => {
-                if player.single() == *a {
-                    if let Some(enemy) = enemies.iter().find(|x| x.0 == *b) {
-                        despawn_enemy(&mut commands, &audio, &audio_assets, enemy);
-                    }
-                }
-                if player.single() == *b {
-                    if let Some(enemy) = enemies.iter().find(|x| x.0 == *a) {
-                        despawn_enemy(&mut commands, &audio, &audio_assets, enemy);
-                    }
-                }
-            }
-            _ => {}
+    if core.reflect_radius <= 0. {
+        return;
+    }
+    for enemy in enemies.iter_mut() {
+        if enemy.1.translation.distance(core_transform.translation) < core.reflect_radius
+            && hit_enemy(commands, balance, rng, combo, enemy)
+        {
+            sound_effects.send(SoundEffect::Attack);
         }
-        println!("Received collision event: {:?}", collision_event);
     }
+}
 
-    for contact_force_event in contact_force_events.iter() {
-        println!("Received contact force event: {:?}", contact_force_event);
+/// Takes one HP from the enemy. When it dies, drops its energy, spawns its split children
+/// and counts towards the [Combo].
+/// Returns whether the enemy was still alive to be hit, the caller plays the sound then.
+pub fn hit_enemy(
+    commands: &mut Commands,
+    balance: &Balance,
+    rng: &mut GameRng,
+    combo: &mut Combo,
+    (entity, transform, mut enemy): (Entity, &Transform, Mut<Enemy>),
+) -> bool {
+    // Already killed earlier in this tick, its despawn is not applied yet
+    if enemy.hp <= 0 {
+        return false;
     }
+    enemy.hp -= 1;
+    if enemy.hp > 0 {
+        return true;
+    }
+    commands.entity(entity).despawn();
+    combo.register_kill();
+    let archetype = enemy.kind.archetype(balance);
+    let orbs = archetype
+        .drops
+        .choose_weighted(&mut rng.drops, |drop| drop.1)
+        .map_or(0, |drop| drop.0);
+    let multiplier = combo.multiplier();
+    for _ in 0..orbs {
+        spawn_energy(commands, rng, transform, archetype.drop_tier, multiplier);
+    }
+    if let Some((kind, count)) = archetype.split {
+        for i in 0..count {
+            let offset = (i as f32 - (count - 1) as f32 / 2.) * archetype.radius;
+            spawn_enemy(
+                commands,
+                balance,
+                kind,
+                Transform::from_translation(transform.translation + Vec3::X * offset),
+            );
+        }
+    }
+    true
 }
 
-fn despawn_enemy(
+fn spawn_energy(
     commands: &mut Commands,
-    audio: &Res<bevy_kira_audio::AudioChannel<bevy_kira_audio::MainTrack>>,
-    audio_assets: &Res<AudioAssets>,
-    enemy: (Entity, &Transform),
+    rng: &mut GameRng,
+    transform: &Transform,
+    tier: EnergyTier,
+    multiplier: i32,
 ) {
-    commands.entity(enemy.0).despawn();
-    audio.play(audio_assets.attack.clone());
-    let mut rand = rand::thread_rng();
+    let rand = &mut rng.energy_scatter;
     let linvel = Vec2::new(rand.gen_range(-1.0..1.0), rand.gen_range(0.0..1.0)).normalize() * 200.0;
     commands
-        .spawn_bundle(EnergyBundle::default())
-        .insert_bundle(TransformBundle::from(enemy.1.clone()))
+        .spawn_bundle(EnergyBundle::new(tier, multiplier))
+        .insert(DespawnOnExit(GameState::Playing))
+        .insert_bundle(TransformBundle::from(*transform))
         .insert(RigidBody::KinematicVelocityBased)
+        .insert(InterpolatedTransform::default())
         .insert(Velocity {
             linvel,
             ..default()
         });
 }
 
+fn explode_enemies(
+    mut commands: Commands,
+    mut enemies: Query<(Entity, &Transform, &mut Enemy), Without<Core>>,
+    mut core: Query<(&Transform, &mut Core)>,
+    balance: Res<Balance>,
+    mut sound_effects: EventWriter<SoundEffect>,
+    mut rng: ResMut<GameRng>,
+    mut combo: ResMut<Combo>,
+) {
+    let (core_transform, mut core) = core.single_mut();
+    let mut exploded = false;
+    for (entity, transform, mut enemy) in enemies.iter_mut() {
+        if enemy.hp <= 0 {
+            continue;
+        }
+        if let Some(explosion) = &enemy.kind.archetype(&balance).explosion {
+            if transform.translation.distance(core_transform.translation) < explosion.radius {
+                enemy.hp = 0;
+                commands.entity(entity).despawn();
+                core.hp -= explosion.damage;
+                sound_effects.send(SoundEffect::Attack);
+                exploded = true;
+            }
+        }
+    }
+    if exploded {
+        reflect_damage(
+            &mut commands,
+            &balance,
+            &mut sound_effects,
+            &mut rng,
+            &mut combo,
+            core_transform,
+            &core,
+            &mut enemies,
+        );
+    }
+}
+
 // Energy
+/// Orbs come in three sizes, bigger ones are worth more
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum EnergyTier {
+    Small,
+    Medium,
+    Large,
+}
+
+impl EnergyTier {
+    pub fn value(self) -> i32 {
+        match self {
+            EnergyTier::Small => 1,
+            EnergyTier::Medium => 2,
+            EnergyTier::Large => 4,
+        }
+    }
+
+    fn radius(self) -> f32 {
+        match self {
+            EnergyTier::Small => 7.,
+            EnergyTier::Medium => 10.,
+            EnergyTier::Large => 14.,
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            EnergyTier::Small => Color::YELLOW_GREEN,
+            EnergyTier::Medium => Color::AQUAMARINE,
+            EnergyTier::Large => Color::FUCHSIA,
+        }
+    }
+}
+
 #[derive(PartialEq)]
 enum EnergyState {
     Created { remaining_time: f32 },
@@ -299,13 +415,16 @@ enum EnergyState {
 #[derive(Component)]
 struct Energy {
     energy: i32,
+    /// [Combo::multiplier] when the enemy dropping it was killed
+    multiplier: i32,
     state: EnergyState,
 }
 
-impl Default for Energy {
-    fn default() -> Self {
+impl Energy {
+    fn new(tier: EnergyTier, multiplier: i32) -> Self {
         Self {
-            energy: 1,
+            energy: tier.value(),
+            multiplier,
             state: EnergyState::Created {
                 remaining_time: 0.3,
             },
@@ -320,19 +439,17 @@ struct EnergyBundle {
     shape_bundle: ShapeBundle,
 }
 
-impl Default for EnergyBundle {
-    fn default() -> Self {
+impl EnergyBundle {
+    fn new(tier: EnergyTier, multiplier: i32) -> Self {
         let shape = shapes::Circle {
-            radius: 10.,
+            radius: tier.radius(),
             ..default()
         };
         Self {
-            energy: default(),
+            energy: Energy::new(tier, multiplier),
             shape_bundle: GeometryBuilder::build_as(
                 &shape,
-                DrawMode::Fill(bevy_prototype_lyon::prelude::FillMode::color(
-                    Color::YELLOW_GREEN,
-                )),
+                DrawMode::Fill(bevy_prototype_lyon::prelude::FillMode::color(tier.color())),
                 Transform::default(),
             ),
         }
@@ -342,12 +459,11 @@ impl Default for EnergyBundle {
 fn move_energy(
     mut query: Query<(&mut Velocity, &Transform, &mut Energy)>,
     target: Query<&Transform, With<Core>>,
-    time: Res<Time>,
 ) {
     for (mut velocity, transform, mut energy) in query.iter_mut() {
         match energy.state {
             EnergyState::Created { remaining_time } => {
-                let new_time = remaining_time - time.delta_seconds();
+                let new_time = remaining_time - FIXED_TIMESTEP;
                 if new_time.is_sign_negative() {
                     velocity.linvel = Vec2::ZERO;
                     continue;
@@ -357,16 +473,16 @@ fn move_energy(
                 }
             }
             EnergyState::Horming { goal_time } => {
-                if goal_time - time.delta_seconds() < 0.0 {
+                if goal_time - FIXED_TIMESTEP < 0.0 {
                     energy.state = EnergyState::Goal;
                     velocity.linvel = Vec2::ZERO;
                     continue;
                 }
                 let diff = (target.single().translation - transform.translation).truncate();
                 let acc = (diff - velocity.linvel * goal_time) * 2.0 / (goal_time * goal_time);
-                velocity.linvel = velocity.linvel + acc * time.delta_seconds();
+                velocity.linvel += acc * FIXED_TIMESTEP;
                 energy.state = EnergyState::Horming {
-                    goal_time: goal_time - time.delta_seconds(),
+                    goal_time: goal_time - FIXED_TIMESTEP,
                 }
             }
             _ => (),
@@ -377,14 +493,13 @@ fn move_energy(
 fn collect_energy(
     mut commands: Commands,
     query: Query<(&Energy, Entity)>,
-    audio: Res<Audio>,
-    audio_assets: Res<AudioAssets>,
+    mut sound_effects: EventWriter<SoundEffect>,
     mut score: ResMut<EnergyPoint>,
 ) {
     for (energy, entity) in query.iter().filter(|x| x.0.state == EnergyState::Goal) {
         commands.entity(entity).despawn();
-        audio.play(audio_assets.collect.clone());
-        score.0 += energy.energy;
+        sound_effects.send(SoundEffect::Collect);
+        score.0 += energy.energy * energy.multiplier;
     }
 }
 
@@ -397,21 +512,22 @@ fn start_collect(
     }
     let (area, area_transform) = area_query.single();
     for (mut energy, transform) in query.iter_mut() {
-        match energy.state {
-            EnergyState::Created { remaining_time: _ } => {
-                if (area_transform.translation() - transform.translation).length_squared()
-                    < area.radius.powi(2)
-                {
-                    energy.state = EnergyState::Horming { goal_time: 1.0 }
+        if let EnergyState::Created { remaining_time: _ } = energy.state {
+            if (area_transform.translation() - transform.translation).length_squared()
+                < area.radius.powi(2)
+            {
+                energy.state = EnergyState::Horming {
+                    goal_time: 1.0 / area.homing_speed,
                 }
             }
-            _ => {}
         }
     }
 }
 
 fn check_gameover(query: Query<&Core>, mut state: ResMut<State<GameState>>) {
     if query.single().hp <= 0 {
-        state.set(GameState::GameOver).unwrap()
+        // Pushed so the run stays on screen, frozen, behind the results.
+        // Fails when the editor was opened during the same frame, which ends the run anyway.
+        let _ = state.push(GameState::GameOver);
     }
 }
@@ -0,0 +1,327 @@
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use super::balance::Balance;
+use super::level::{Level, Levels, Tile};
+use super::player::JumpArc;
+use super::rng::GameSeed;
+
+const WIDTH: usize = 32;
+const HEIGHT: usize = 18;
+const TILE_SIZE: f32 = 40.;
+/// Share of the jump arc the generator relies on, the rest absorbs the player's width and
+/// imperfect jumps
+const JUMP_MARGIN: f32 = 0.8;
+/// Platforms never cover the columns this close to the core, walkers would get stuck on them
+const CORE_CLEARANCE: usize = 2;
+/// Rows at the top kept for enemy spawn zones
+const SPAWN_ROWS: usize = 3;
+const PLATFORMS: std::ops::RangeInclusive<usize> = 6..=10;
+const PLATFORM_WIDTH: std::ops::RangeInclusive<usize> = 2..=5;
+/// Layouts tried before falling back to a bare floor
+const ATTEMPTS: usize = 20;
+
+/// A run of cells the player can stand in, on top of solid tiles or one-way platforms
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Surface {
+    /// The row of the cells the player stands in, above the supporting tiles
+    pub row: usize,
+    pub start: usize,
+    /// Last column of the run, included
+    pub end: usize,
+}
+
+impl Surface {
+    /// Empty columns between the two runs, 0 when they overlap
+    fn gap(&self, other: &Surface) -> usize {
+        if other.start > self.end {
+            other.start - self.end - 1
+        } else if self.start > other.end {
+            self.start - other.end - 1
+        } else {
+            0
+        }
+    }
+
+    fn can_jump_to(&self, other: &Surface, tile_size: f32, arc: &JumpArc) -> bool {
+        let rise = (self.row as f32 - other.row as f32) * tile_size;
+        arc.reach(rise)
+            .is_some_and(|reach| self.gap(other) as f32 * tile_size <= reach)
+    }
+}
+
+fn supports(tile: Tile) -> bool {
+    matches!(tile, Tile::Solid | Tile::OneWay)
+}
+
+fn standable(level: &Level, column: usize, row: usize) -> bool {
+    row + 1 < level.height()
+        && !supports(level.tile(column, row))
+        && supports(level.tile(column, row + 1))
+}
+
+pub fn surfaces(level: &Level) -> Vec<Surface> {
+    let mut surfaces = Vec::new();
+    for row in 0..level.height() {
+        let mut run: Option<Surface> = None;
+        for column in 0..level.width() {
+            if standable(level, column, row) {
+                match &mut run {
+                    Some(surface) => surface.end = column,
+                    None => {
+                        run = Some(Surface {
+                            row,
+                            start: column,
+                            end: column,
+                        })
+                    }
+                }
+            } else {
+                surfaces.extend(run.take());
+            }
+        }
+        surfaces.extend(run);
+    }
+    surfaces
+}
+
+/// Checks that the player can get onto every surface of the level from where it appears,
+/// by walking, dropping and jumping. Ceilings are not taken into account, so a level passing
+/// here can still have a surface only reachable through a wall.
+/// Returns the unreachable surfaces otherwise.
+pub fn check_reachable(level: &Level, arc: &JumpArc) -> Result<(), Vec<Surface>> {
+    let surfaces = surfaces(level);
+    let (column, mut row) = level.find(Tile::PlayerStart).unwrap_or_default();
+    while row < level.height() && !standable(level, column, row) {
+        row += 1;
+    }
+    let mut reached = vec![false; surfaces.len()];
+    let mut queue: Vec<usize> = surfaces
+        .iter()
+        .position(|surface| surface.row == row && (surface.start..=surface.end).contains(&column))
+        .into_iter()
+        .collect();
+    while let Some(from) = queue.pop() {
+        if reached[from] {
+            continue;
+        }
+        reached[from] = true;
+        queue.extend((0..surfaces.len()).filter(|&to| {
+            !reached[to] && surfaces[from].can_jump_to(&surfaces[to], level.tile_size, arc)
+        }));
+    }
+    let unreachable: Vec<Surface> = surfaces
+        .into_iter()
+        .zip(reached)
+        .filter_map(|(surface, reached)| (!reached).then_some(surface))
+        .collect();
+    if unreachable.is_empty() {
+        Ok(())
+    } else {
+        Err(unreachable)
+    }
+}
+
+/// Lays out a level from this seed: a floor with the core and the player on it, one-way
+/// platforms the player can jump between, and enemy spawn zones away from the core.
+/// The same seed and jump arc always give the same level.
+pub fn procedural_level(seed: u64, arc: &JumpArc) -> Level {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut level = (0..ATTEMPTS)
+        .map(|_| layout(&mut rng, arc, true))
+        .find(|level| check_reachable(level, arc).is_ok())
+        .unwrap_or_else(|| layout(&mut rng, arc, false));
+    level.name = format!("Random #{seed}");
+    level.path = format!("levels/random-{seed}.level.ron");
+    level.seed = Some(seed);
+    level
+}
+
+fn layout(rng: &mut StdRng, arc: &JumpArc, with_platforms: bool) -> Level {
+    let mut grid = vec![vec![Tile::Empty; WIDTH]; HEIGHT];
+    let floor = HEIGHT - 1;
+    grid[floor] = vec![Tile::Solid; WIDTH];
+
+    let core = rng.gen_range(WIDTH / 3..2 * WIDTH / 3);
+    let near_core = core - CORE_CLEARANCE..=core + CORE_CLEARANCE;
+    grid[floor - 1][core] = Tile::Core;
+    let player = *(0..WIDTH)
+        .filter(|column| column.abs_diff(core) > CORE_CLEARANCE + 1)
+        .collect::<Vec<_>>()
+        .choose(rng)
+        .expect("the grid is wider than the core's surroundings");
+    grid[floor - 1][player] = Tile::PlayerStart;
+
+    // Enemies come from the top corner farthest from the core, and the other one when it is far
+    // enough too
+    let spawn_width = WIDTH / 5;
+    let corners = [(0, core), (WIDTH - spawn_width, WIDTH - 1 - core)];
+    let farthest = core.max(WIDTH - 1 - core);
+    for (start, distance) in corners {
+        if distance == farthest || distance >= 2 * spawn_width {
+            for row in &mut grid[1..SPAWN_ROWS] {
+                row[start..start + spawn_width].fill(Tile::EnemySpawn);
+            }
+        }
+    }
+
+    if with_platforms {
+        let max_rise = (arc.height() * JUMP_MARGIN / TILE_SIZE) as usize;
+        let mut stands = vec![Surface {
+            row: floor - 1,
+            start: 0,
+            end: WIDTH - 1,
+        }];
+        let target = rng.gen_range(PLATFORMS);
+        for _ in 0..target * 50 {
+            if stands.len() > target {
+                break;
+            }
+            let from = *stands.choose(rng).expect("the floor is always there");
+            let rise = rng.gen_range(2..=max_rise.max(2));
+            // The platform tile sits right below the cells the player stands in
+            let Some(platform_row) = (from.row + 1).checked_sub(rise) else {
+                continue;
+            };
+            if platform_row < SPAWN_ROWS + 2 {
+                continue;
+            }
+            let reach = arc.reach(rise as f32 * TILE_SIZE).unwrap_or(0.) * JUMP_MARGIN;
+            let gap = (reach / TILE_SIZE) as usize;
+            let width = rng.gen_range(PLATFORM_WIDTH);
+            let first = from.start.saturating_sub(gap + width);
+            let last = (from.end + gap + 1).min(WIDTH - width);
+            let start = rng.gen_range(first..=last);
+            let end = start + width - 1;
+            if near_core
+                .clone()
+                .any(|column| (start..=end).contains(&column))
+            {
+                continue;
+            }
+            // Leave headroom above and a free row below, so platforms never stack up
+            let crowded = (platform_row - 2..=(platform_row + 1).min(floor - 1)).any(|row| {
+                grid[row][start.saturating_sub(1)..=(end + 1).min(WIDTH - 1)]
+                    .iter()
+                    .any(|&tile| tile != Tile::Empty)
+            });
+            if crowded {
+                continue;
+            }
+            grid[platform_row][start..=end].fill(Tile::OneWay);
+            stands.push(Surface {
+                row: platform_row - 1,
+                start,
+                end,
+            });
+        }
+    }
+
+    Level {
+        name: String::new(),
+        tile_size: TILE_SIZE,
+        rows: grid
+            .into_iter()
+            .map(|row| row.into_iter().map(Tile::to_char).collect())
+            .collect(),
+        path: String::new(),
+        seed: None,
+    }
+}
+
+/// Lays the procedural level out again from the run's seed, before the level gets built
+pub fn generate_level(mut levels: ResMut<Levels>, seed: Res<GameSeed>, balance: Res<Balance>) {
+    let selected = levels.selected;
+    if levels.levels[selected].seed.is_some() {
+        levels.levels[selected] = procedural_level(seed.0, &JumpArc::new(&balance.player));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arc() -> JumpArc {
+        JumpArc::new(&Balance::default().player)
+    }
+
+    fn level(rows: &[&str]) -> Level {
+        Level {
+            name: "Test".to_string(),
+            tile_size: TILE_SIZE,
+            rows: rows.iter().map(|row| row.to_string()).collect(),
+            path: String::new(),
+            seed: None,
+        }
+    }
+
+    #[test]
+    fn every_seed_gives_a_reachable_valid_level() {
+        let arc = arc();
+        for seed in 0..500 {
+            let level = procedural_level(seed, &arc);
+            assert_eq!(check_reachable(&level, &arc), Ok(()), "seed {seed}");
+            assert_eq!(level.validate(), Ok(()), "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_layout() {
+        let arc = arc();
+        assert_eq!(
+            procedural_level(42, &arc).rows,
+            procedural_level(42, &arc).rows
+        );
+    }
+
+    #[test]
+    fn reports_a_platform_out_of_reach() {
+        let mut rows = vec!["........"; 12];
+        rows[1] = "--......";
+        rows[10] = "P.......";
+        rows[11] = "########";
+        let result = check_reachable(&level(&rows), &arc());
+        assert_eq!(
+            result,
+            Err(vec![Surface {
+                row: 0,
+                start: 0,
+                end: 1
+            }])
+        );
+
+        rows[1] = "........";
+        assert_eq!(check_reachable(&level(&rows), &arc()), Ok(()));
+    }
+
+    #[test]
+    fn core_rests_on_the_floor() {
+        let arc = arc();
+        for seed in 0..100 {
+            let level = procedural_level(seed, &arc);
+            let (column, row) = level.find(Tile::Core).expect("a core");
+            assert_eq!(row, HEIGHT - 2, "seed {seed}");
+            assert_eq!(level.tile(column, row + 1), Tile::Solid, "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn spawn_zones_keep_away_from_the_core() {
+        let arc = arc();
+        for seed in 0..100 {
+            let level = procedural_level(seed, &arc);
+            let (core, _) = level.find(Tile::Core).expect("a core");
+            let spawns: Vec<(usize, usize)> = (0..HEIGHT)
+                .flat_map(|row| (0..WIDTH).map(move |column| (column, row)))
+                .filter(|&(column, row)| level.tile(column, row) == Tile::EnemySpawn)
+                .collect();
+            assert!(!spawns.is_empty(), "seed {seed}");
+            for (column, row) in spawns {
+                assert!(row < SPAWN_ROWS, "seed {seed}");
+                assert!(column.abs_diff(core) >= WIDTH / 5, "seed {seed}");
+            }
+        }
+    }
+}
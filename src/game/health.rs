@@ -0,0 +1,173 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
+
+use super::balance::Balance;
+use super::collision::EnemyHitPlayer;
+use super::enemy::Enemy;
+use super::game::{Core, EnergyPoint};
+use super::player::Player;
+use super::schedule::{InterpolatedTransform, FIXED_TIMESTEP};
+
+/// Seconds between two blinks of the player while invulnerable
+const BLINK_INTERVAL: f32 = 0.1;
+
+/// How the player takes hits and comes back, part of the [Balance] file.
+/// Durations are in seconds.
+#[derive(Clone, Debug, Deserialize)]
+pub struct PlayerHealthConfig {
+    pub max_hp: i32,
+    /// HP lost when an enemy touches the player
+    pub contact_damage: i32,
+    /// Impulse pushing the player away from the enemy that hit it, `x` points away from the enemy
+    pub knockback: Vec2,
+    /// Time after a hit during which the player cannot be hurt again
+    pub invulnerability: f32,
+    /// Time before a defeated player comes back next to the core
+    pub respawn_delay: f32,
+    /// Energy lost every time the player is defeated
+    pub respawn_energy_loss: i32,
+    /// Where the player comes back, relative to the core
+    pub respawn_offset: Vec2,
+}
+
+#[derive(Component)]
+pub struct PlayerHealth {
+    pub hp: i32,
+    /// Time left before the player can be hurt again
+    invulnerable_timer: f32,
+}
+
+impl PlayerHealth {
+    pub fn new(hp: i32) -> Self {
+        Self {
+            hp,
+            invulnerable_timer: 0.,
+        }
+    }
+}
+
+/// Put on a defeated player until it comes back. The player is hidden and out of the physics
+/// meanwhile, the systems moving it ignore players with this component.
+#[derive(Component)]
+pub struct Respawning {
+    /// Time left before the player comes back
+    pub remaining: f32,
+}
+
+#[allow(clippy::type_complexity)]
+pub fn damage_player(
+    mut commands: Commands,
+    balance: Res<Balance>,
+    mut hit_events: EventReader<EnemyHitPlayer>,
+    mut energy: ResMut<EnergyPoint>,
+    mut players: Query<
+        (
+            &mut Transform,
+            &mut Velocity,
+            &mut ExternalImpulse,
+            &mut PlayerHealth,
+            &mut Visibility,
+            &mut InterpolatedTransform,
+        ),
+        (With<Player>, Without<Respawning>),
+    >,
+    enemies: Query<&Transform, (With<Enemy>, Without<Player>)>,
+) {
+    let config = &balance.health;
+    for (_, _, _, mut health, mut visibility, _) in players.iter_mut() {
+        if health.invulnerable_timer > 0. {
+            health.invulnerable_timer = (health.invulnerable_timer - FIXED_TIMESTEP).max(0.);
+            visibility.is_visible = (health.invulnerable_timer / BLINK_INTERVAL) as i32 % 2 == 0;
+        } else {
+            visibility.is_visible = true;
+        }
+    }
+
+    for event in hit_events.iter() {
+        let (
+            mut transform,
+            mut velocity,
+            mut impulse,
+            mut health,
+            mut visibility,
+            mut interpolated,
+        ) = match players.get_mut(event.player) {
+            Ok(player) => player,
+            Err(_) => continue,
+        };
+        if health.invulnerable_timer > 0. {
+            continue;
+        }
+        let enemy_transform = match enemies.get(event.enemy) {
+            Ok(enemy_transform) => enemy_transform,
+            Err(_) => continue,
+        };
+
+        health.hp = (health.hp - config.contact_damage).max(0);
+        health.invulnerable_timer = config.invulnerability;
+        if health.hp > 0 {
+            let away = if transform.translation.x < enemy_transform.translation.x {
+                -1.
+            } else {
+                1.
+            };
+            velocity.linvel = Vec2::ZERO;
+            impulse.impulse = Vec2::new(away * config.knockback.x, config.knockback.y);
+            continue;
+        }
+
+        energy.0 = (energy.0 - config.respawn_energy_loss).max(0);
+        velocity.linvel = Vec2::ZERO;
+        visibility.is_visible = false;
+        // Parked out of reach, the core may have moved by the time the player comes back
+        transform.translation.y = -10_000.;
+        interpolated.snap(*transform);
+        commands
+            .entity(event.player)
+            .insert(Respawning {
+                remaining: config.respawn_delay,
+            })
+            .insert(RigidBody::Fixed)
+            .insert(Sensor);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn respawn_player(
+    mut commands: Commands,
+    balance: Res<Balance>,
+    mut players: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut PlayerHealth,
+            &mut Visibility,
+            &mut Respawning,
+            &mut InterpolatedTransform,
+        ),
+        Without<Core>,
+    >,
+    core: Query<&Transform, With<Core>>,
+) {
+    let config = &balance.health;
+    for (entity, mut transform, mut health, mut visibility, mut respawning, mut interpolated) in
+        players.iter_mut()
+    {
+        respawning.remaining -= FIXED_TIMESTEP;
+        if respawning.remaining > 0. {
+            continue;
+        }
+        let core = core.single().translation;
+        transform.translation = core + config.respawn_offset.extend(0.);
+        interpolated.snap(*transform);
+        health.hp = config.max_hp;
+        health.invulnerable_timer = config.invulnerability;
+        visibility.is_visible = true;
+        commands
+            .entity(entity)
+            .remove::<Respawning>()
+            .remove::<Sensor>()
+            .insert(RigidBody::Dynamic);
+    }
+}
@@ -0,0 +1,307 @@
+use super::combo::Combo;
+use super::enemy::Enemy;
+use super::game::{Core, EnergyPoint};
+use super::health::{PlayerHealth, Respawning};
+use super::rng::GameSeed;
+use super::wave::{Wave, WavePhase};
+use crate::cleanup::DespawnOnExit;
+use crate::display::{AnchoredToView, ScaledText};
+use crate::loading::FontAssets;
+use crate::GameState;
+
+use bevy::prelude::*;
+
+/// Draws the core HP, player HP, energy, combo, wave and seed texts.
+/// Kept apart from [`super::MainGamePlugin`] so the gameplay can run without fonts or a renderer.
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_enter(GameState::Playing)
+                .with_system(setup_top_bar)
+                .with_system(setup_player_hp)
+                .with_system(setup_wave)
+                .with_system(setup_seed),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(update_hp_text)
+                .with_system(update_player_hp_text)
+                .with_system(update_score)
+                .with_system(update_combo_text)
+                .with_system(update_wave_text),
+        );
+    }
+}
+
+/// The core HP, energy and combo, along the top of the view.
+/// They wrap onto more lines when the view is too narrow for them.
+fn setup_top_bar(mut commands: Commands, font_assets: Res<FontAssets>) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(0.0),
+                    right: Val::Px(0.0),
+                    top: Val::Px(0.0),
+                    ..default()
+                },
+                // The UI's vertical axis points up, reversed lines go down from the top
+                flex_wrap: FlexWrap::WrapReverse,
+                align_items: AlignItems::FlexEnd,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .insert(DespawnOnExit(GameState::Playing))
+        .insert(AnchoredToView::default())
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(hp_text(&font_assets))
+                .insert(ScaledText::default())
+                .insert(HpText);
+            parent
+                .spawn_bundle(energy_text(&font_assets))
+                .insert(ScaledText::default())
+                .insert(EnergyText);
+            parent
+                .spawn_bundle(combo_text(&font_assets))
+                .insert(ScaledText::default())
+                .insert(ComboText);
+        });
+}
+
+#[derive(Component)]
+struct EnergyText;
+
+fn energy_text(font_assets: &FontAssets) -> TextBundle {
+    TextBundle::from_sections([
+        TextSection::new(
+            "Energy: ",
+            TextStyle {
+                font: font_assets.fira_sans.clone(),
+                font_size: 60.0,
+                color: Color::WHITE,
+            },
+        ),
+        TextSection::from_style(TextStyle {
+            font: font_assets.fira_sans.clone(),
+            font_size: 60.0,
+            color: Color::GOLD,
+        }),
+    ])
+}
+
+fn update_score(mut query: Query<&mut Text, With<EnergyText>>, score: Res<EnergyPoint>) {
+    let s = score.0;
+    for mut text in &mut query {
+        text.sections[1].value = format!("{s:.2}");
+    }
+}
+
+#[derive(Component)]
+struct ComboText;
+
+fn combo_text(font_assets: &FontAssets) -> TextBundle {
+    TextBundle::from_section(
+        "",
+        TextStyle {
+            font: font_assets.fira_sans.clone(),
+            font_size: 40.0,
+            color: Color::GOLD,
+        },
+    )
+}
+
+/// Shown from the second kill of a chain
+fn update_combo_text(mut query: Query<&mut Text, With<ComboText>>, combo: Res<Combo>) {
+    if !combo.is_changed() {
+        return;
+    }
+    let value = if combo.kills > 1 {
+        format!("  {} combo x{}", combo.kills, combo.multiplier())
+    } else {
+        String::new()
+    };
+    for mut text in &mut query {
+        text.sections[0].value = value.clone();
+    }
+}
+
+#[derive(Component)]
+struct HpText;
+
+fn hp_text(font_assets: &FontAssets) -> TextBundle {
+    TextBundle::from_sections([
+        TextSection::new(
+            "HP: ",
+            TextStyle {
+                font: font_assets.fira_sans.clone(),
+                font_size: 60.0,
+                color: Color::WHITE,
+            },
+        ),
+        TextSection::from_style(TextStyle {
+            font: font_assets.fira_sans.clone(),
+            font_size: 60.0,
+            color: Color::GOLD,
+        }),
+    ])
+}
+
+fn update_hp_text(mut query: Query<&mut Text, With<HpText>>, core: Query<&Core>) {
+    let core = core.single();
+    for mut text in &mut query {
+        text.sections[1].value = format!("{}/{}", core.hp, core.max_hp);
+    }
+}
+
+#[derive(Component)]
+struct PlayerHpText;
+
+fn setup_player_hp(mut commands: Commands, font_assets: Res<FontAssets>) {
+    commands
+        .spawn()
+        .insert(DespawnOnExit(GameState::Playing))
+        .insert_bundle(
+            TextBundle::from_sections([
+                TextSection::new(
+                    "Player: ",
+                    TextStyle {
+                        font: font_assets.fira_sans.clone(),
+                        font_size: 30.0,
+                        color: Color::WHITE,
+                    },
+                ),
+                TextSection::from_style(TextStyle {
+                    font: font_assets.fira_sans.clone(),
+                    font_size: 30.0,
+                    color: Color::CYAN,
+                }),
+            ])
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(10.0),
+                    bottom: Val::Px(45.0),
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(ScaledText::default())
+        .insert(AnchoredToView::default())
+        .insert(PlayerHpText);
+}
+
+fn update_player_hp_text(
+    mut query: Query<&mut Text, With<PlayerHpText>>,
+    player: Query<(&PlayerHealth, Option<&Respawning>)>,
+) {
+    let status = match player.get_single() {
+        Ok((_, Some(respawning))) => format!("back in {:.0}", respawning.remaining.ceil()),
+        Ok((health, None)) => format!("{} HP", health.hp),
+        Err(_) => return,
+    };
+    for mut text in &mut query {
+        text.sections[1].value = status.clone();
+    }
+}
+
+#[derive(Component)]
+struct WaveText;
+
+fn setup_wave(mut commands: Commands, font_assets: Res<FontAssets>) {
+    commands
+        .spawn()
+        .insert(DespawnOnExit(GameState::Playing))
+        .insert_bundle(
+            TextBundle::from_sections([
+                TextSection::new(
+                    "Wave: ",
+                    TextStyle {
+                        font: font_assets.fira_sans.clone(),
+                        font_size: 30.0,
+                        color: Color::WHITE,
+                    },
+                ),
+                TextSection::from_style(TextStyle {
+                    font: font_assets.fira_sans.clone(),
+                    font_size: 30.0,
+                    color: Color::GOLD,
+                }),
+                TextSection::from_style(TextStyle {
+                    font: font_assets.fira_sans.clone(),
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                }),
+            ])
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(10.0),
+                    bottom: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(ScaledText::default())
+        .insert(AnchoredToView::default())
+        .insert(WaveText);
+}
+
+fn update_wave_text(
+    mut query: Query<&mut Text, With<WaveText>>,
+    wave: Res<Wave>,
+    enemies: Query<(), With<Enemy>>,
+) {
+    let status = match wave.phase {
+        WavePhase::Resting if wave.number == 0 => {
+            format!("  starts in {:.0}", wave.rest_remaining().ceil())
+        }
+        WavePhase::Resting => format!("  cleared! next in {:.0}", wave.rest_remaining().ceil()),
+        WavePhase::Spawning | WavePhase::Fighting => {
+            let left = wave.enemy_count - wave.spawned + enemies.iter().count() as u32;
+            format!("  {left} left")
+        }
+    };
+    for mut text in &mut query {
+        text.sections[1].value = wave.number.to_string();
+        text.sections[2].value = status.clone();
+    }
+}
+
+#[derive(Component)]
+struct SeedText;
+
+fn setup_seed(mut commands: Commands, font_assets: Res<FontAssets>, seed: Res<GameSeed>) {
+    commands
+        .spawn()
+        .insert(DespawnOnExit(GameState::Playing))
+        .insert_bundle(
+            TextBundle::from_section(
+                format!("Seed: {}", seed.0),
+                TextStyle {
+                    font: font_assets.fira_sans.clone(),
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    right: Val::Px(10.0),
+                    bottom: Val::Px(10.0),
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(ScaledText::default())
+        .insert(AnchoredToView::default())
+        .insert(SeedText);
+}
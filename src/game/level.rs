@@ -0,0 +1,682 @@
+use bevy::asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy_prototype_lyon::prelude::*;
+use bevy_rapier2d::prelude::*;
+use bevy_rapier2d::rapier::math::Vector;
+use serde::{Deserialize, Serialize};
+
+use super::balance::Balance;
+use super::generator::procedural_level;
+use super::player::JumpArc;
+use super::rng::GameSeed;
+use crate::cleanup::DespawnOnExit;
+use crate::GameState;
+
+/// One cell of a [Level], written as a single character in the level file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tile {
+    Empty,
+    /// Blocks from every side
+    Solid,
+    /// Can be jumped through from below and stood on from above
+    OneWay,
+    /// Where the player appears, standing on the bottom of the tile
+    PlayerStart,
+    /// Where the core appears, resting on the bottom of the tile
+    Core,
+    /// Enemies appear anywhere inside these tiles
+    EnemySpawn,
+}
+
+impl Tile {
+    pub fn from_char(c: char) -> Option<Tile> {
+        match c {
+            '.' => Some(Tile::Empty),
+            '#' => Some(Tile::Solid),
+            '-' => Some(Tile::OneWay),
+            'P' => Some(Tile::PlayerStart),
+            'C' => Some(Tile::Core),
+            'E' => Some(Tile::EnemySpawn),
+            _ => None,
+        }
+    }
+
+    pub fn to_char(self) -> char {
+        match self {
+            Tile::Empty => '.',
+            Tile::Solid => '#',
+            Tile::OneWay => '-',
+            Tile::PlayerStart => 'P',
+            Tile::Core => 'C',
+            Tile::EnemySpawn => 'E',
+        }
+    }
+}
+
+/// A rectangle of tiles, `column` and `row` being its top left one
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TileRect {
+    pub column: usize,
+    pub row: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl TileRect {
+    pub fn contains(&self, column: usize, row: usize) -> bool {
+        (self.column..self.column + self.width).contains(&column)
+            && (self.row..self.row + self.height).contains(&row)
+    }
+}
+
+/// A rectangle enemies appear in, picked uniformly
+#[derive(Clone, Debug)]
+pub struct SpawnZone {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+/// The spawn zones of the level being played, worked out once when a run starts
+pub struct EnemyZones(pub Vec<SpawnZone>);
+
+/// Written on top of saved level files
+const LEVEL_FILE_HEADER: &str = "// Tiles: '.' empty, '#' solid, '-' one-way platform, \
+'P' player start, 'C' core, 'E' enemy spawn zone\n";
+
+/// A level drawn as a grid of [Tile] characters, one string per row from top to bottom.
+/// The grid is centered on the origin of the world. Loaded from `assets/levels/*.level.ron`.
+#[derive(Clone, Debug, Deserialize, Serialize, TypeUuid)]
+#[uuid = "0c7e3f52-8a4d-4b7e-a0f1-6d2b9c5e8f13"]
+pub struct Level {
+    pub name: String,
+    /// Side of one tile, in pixels
+    pub tile_size: f32,
+    pub rows: Vec<String>,
+    /// The file the level was read from, relative to the assets folder
+    #[serde(skip)]
+    pub path: String,
+    /// The seed a procedural level was laid out from, `None` for level files.
+    /// Such a level is laid out again from [GameSeed] whenever a run starts on it.
+    #[serde(skip)]
+    pub seed: Option<u64>,
+}
+
+impl Level {
+    /// Parses and validates a level file, the editor checks its levels through here too
+    pub fn from_ron(text: &[u8], path: &str) -> Result<Level, String> {
+        let mut level: Level =
+            ron::de::from_bytes(text).map_err(|err| format!("could not parse {path}: {err}"))?;
+        level
+            .validate()
+            .map_err(|errors| format!("invalid level in {path}:\n  - {}", errors.join("\n  - ")))?;
+        level.path = path.to_string();
+        Ok(level)
+    }
+
+    pub fn to_ron(&self) -> String {
+        let body = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new())
+            .expect("a level can always be written");
+        format!("{LEVEL_FILE_HEADER}{body}\n")
+    }
+
+    pub fn width(&self) -> usize {
+        self.rows.first().map_or(0, |row| row.chars().count())
+    }
+
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Size of the whole grid, in pixels
+    pub fn size(&self) -> Vec2 {
+        Vec2::new(self.width() as f32, self.height() as f32) * self.tile_size
+    }
+
+    /// The tile at this position, [Tile::Empty] outside of the grid
+    pub fn tile(&self, column: usize, row: usize) -> Tile {
+        self.rows
+            .get(row)
+            .and_then(|line| line.chars().nth(column))
+            .and_then(Tile::from_char)
+            .unwrap_or(Tile::Empty)
+    }
+
+    /// The tile under a world position, if it is inside the grid
+    pub fn tile_at(&self, position: Vec2) -> Option<(usize, usize)> {
+        let from_top_left = Vec2::new(
+            position.x + self.size().x / 2.,
+            self.size().y / 2. - position.y,
+        ) / self.tile_size;
+        if from_top_left.x < 0. || from_top_left.y < 0. {
+            return None;
+        }
+        let (column, row) = (from_top_left.x as usize, from_top_left.y as usize);
+        (column < self.width() && row < self.height()).then_some((column, row))
+    }
+
+    /// Does nothing outside of the grid
+    pub fn set_tile(&mut self, column: usize, row: usize, tile: Tile) {
+        if let Some(line) = self.rows.get_mut(row) {
+            if column < line.chars().count() {
+                *line = line
+                    .chars()
+                    .enumerate()
+                    .map(|(c, old)| if c == column { tile.to_char() } else { old })
+                    .collect();
+            }
+        }
+    }
+
+    /// The world position of the bottom left corner of a tile rectangle, and of its top right one
+    pub fn bounds(&self, rect: TileRect) -> (Vec2, Vec2) {
+        let top_left = Vec2::new(-self.size().x / 2., self.size().y / 2.);
+        let min = top_left
+            + Vec2::new(
+                rect.column as f32 * self.tile_size,
+                -((rect.row + rect.height) as f32 * self.tile_size),
+            );
+        let max = min + Vec2::new(rect.width as f32, rect.height as f32) * self.tile_size;
+        (min, max)
+    }
+
+    pub fn find(&self, tile: Tile) -> Option<(usize, usize)> {
+        (0..self.height())
+            .flat_map(|row| (0..self.width()).map(move |column| (column, row)))
+            .find(|&(column, row)| self.tile(column, row) == tile)
+    }
+
+    /// The middle of the bottom edge of the first tile of this kind
+    fn floor_of(&self, tile: Tile) -> Vec2 {
+        let (column, row) = self.find(tile).unwrap_or_default();
+        let (min, max) = self.bounds(TileRect {
+            column,
+            row,
+            width: 1,
+            height: 1,
+        });
+        Vec2::new((min.x + max.x) / 2., min.y)
+    }
+
+    /// Where the player's feet are when it appears
+    pub fn player_start(&self) -> Vec2 {
+        self.floor_of(Tile::PlayerStart)
+    }
+
+    /// Where the bottom of the core is when it appears
+    pub fn core_position(&self) -> Vec2 {
+        self.floor_of(Tile::Core)
+    }
+
+    pub fn enemy_zones(&self) -> Vec<SpawnZone> {
+        self.merge(Tile::EnemySpawn, true)
+            .into_iter()
+            .map(|rect| {
+                let (min, max) = self.bounds(rect);
+                SpawnZone { min, max }
+            })
+            .collect()
+    }
+
+    /// Covers every tile of this kind with as few rectangles as possible.
+    /// Runs of tiles are grown to the right first, then downwards as long as the whole run
+    /// below is made of the same tiles, when `grow_down` is set.
+    pub fn merge(&self, tile: Tile, grow_down: bool) -> Vec<TileRect> {
+        let (width, height) = (self.width(), self.height());
+        let mut covered = vec![vec![false; width]; height];
+        let mut rects = Vec::new();
+        for row in 0..height {
+            for column in 0..width {
+                if covered[row][column] || self.tile(column, row) != tile {
+                    continue;
+                }
+                let free = |covered: &Vec<Vec<bool>>, column: usize, row: usize| {
+                    !covered[row][column] && self.tile(column, row) == tile
+                };
+                let mut rect_width = 1;
+                while column + rect_width < width && free(&covered, column + rect_width, row) {
+                    rect_width += 1;
+                }
+                let mut rect_height = 1;
+                while grow_down
+                    && row + rect_height < height
+                    && (column..column + rect_width).all(|c| free(&covered, c, row + rect_height))
+                {
+                    rect_height += 1;
+                }
+                for covered_row in covered.iter_mut().skip(row).take(rect_height) {
+                    for cell in covered_row.iter_mut().skip(column).take(rect_width) {
+                        *cell = true;
+                    }
+                }
+                rects.push(TileRect {
+                    column,
+                    row,
+                    width: rect_width,
+                    height: rect_height,
+                });
+            }
+        }
+        rects
+    }
+
+    /// Lists every problem of the file instead of stopping at the first one
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        if self.tile_size <= 0. {
+            errors.push(format!(
+                "tile_size must be positive, got {}",
+                self.tile_size
+            ));
+        }
+        if self.width() == 0 {
+            errors.push("rows must contain at least one tile".to_string());
+        }
+        for (i, row) in self.rows.iter().enumerate() {
+            if row.chars().count() != self.width() {
+                errors.push(format!(
+                    "rows[{i}] is {} tiles wide, the first row {}",
+                    row.chars().count(),
+                    self.width()
+                ));
+            }
+            for c in row.chars().filter(|c| Tile::from_char(*c).is_none()) {
+                errors.push(format!("rows[{i}] contains the unknown tile '{c}'"));
+            }
+        }
+        let count = |tile: Tile| {
+            self.rows
+                .iter()
+                .flat_map(|row| row.chars())
+                .filter(|c| *c == tile.to_char())
+                .count()
+        };
+        for tile in [Tile::PlayerStart, Tile::Core] {
+            if count(tile) != 1 {
+                errors.push(format!(
+                    "the level must contain exactly one '{}' tile, got {}",
+                    tile.to_char(),
+                    count(tile)
+                ));
+            }
+        }
+        if count(Tile::EnemySpawn) == 0 {
+            errors.push("the level must contain at least one 'E' tile".to_string());
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Every level that can be played, and the one picked in the menu
+pub struct Levels {
+    pub levels: Vec<Level>,
+    pub selected: usize,
+}
+
+impl Levels {
+    pub fn current(&self) -> &Level {
+        &self.levels[self.selected]
+    }
+
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1) % self.levels.len();
+    }
+
+    /// Swaps the level files for `loaded`, procedural levels are kept after them
+    pub fn replace_files(&mut self, loaded: Vec<Level>) {
+        if loaded.is_empty() {
+            return;
+        }
+        let generated = self.levels.drain(..).filter(|level| level.seed.is_some());
+        self.levels = loaded.into_iter().chain(generated).collect();
+        self.selected = 0;
+    }
+}
+
+/// Calls `$then!` with the paths of the shipped level files, relative to the assets folder,
+/// in the order the menu offers them. The asset collection and the embedded copies below
+/// are both written from this list.
+macro_rules! shipped_levels {
+    ($then:ident) => {
+        $then! {
+            "levels/plains.level.ron",
+            "levels/towers.level.ron"
+        }
+    };
+}
+pub(crate) use shipped_levels;
+
+macro_rules! embedded_levels {
+    ($($path:literal),*) => {
+        [$(($path, include_str!(concat!("../../assets/", $path)))),*]
+    };
+}
+
+/// The copy of the shipped level file at `path` built into the game
+fn embedded_level(path: &str) -> Option<Level> {
+    shipped_levels!(embedded_levels)
+        .into_iter()
+        .find(|(shipped, _)| *shipped == path)
+        .map(|(path, level)| {
+            Level::from_ron(level.as_bytes(), path).expect("a default level file is invalid")
+        })
+}
+
+impl FromWorld for Levels {
+    /// The shipped level files, embedded for runs without an asset server,
+    /// followed by a procedural level
+    fn from_world(world: &mut World) -> Self {
+        let levels = shipped_levels!(embedded_levels)
+            .into_iter()
+            .filter_map(|(path, _)| embedded_level(path))
+            .chain([procedural_level(
+                world.resource::<GameSeed>().0,
+                &JumpArc::new(&world.resource::<Balance>().player),
+            )])
+            .collect();
+        Self {
+            levels,
+            selected: 0,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct LevelLoader;
+
+impl AssetLoader for LevelLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let path = load_context.path().display().to_string();
+            // A failed asset would keep the game on the loading screen forever
+            let level = match (Level::from_ron(bytes, &path), embedded_level(&path)) {
+                (Ok(level), _) => level,
+                (Err(message), Some(embedded)) => {
+                    warn!("{message}\nfalling back to the level shipped with the game");
+                    embedded
+                }
+                (Err(message), None) => {
+                    error!("{message}");
+                    return Err(bevy::asset::Error::msg(message));
+                }
+            };
+            load_context.set_default_asset(LoadedAsset::new(level));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level.ron"]
+    }
+}
+
+/// A platform only blocking what lands on it from above
+#[derive(Component)]
+pub struct OneWayPlatform {
+    /// Height of its top edge
+    pub top: f32,
+}
+
+/// What the physics hooks read from the world, see [OneWayPlatformHooks]
+pub type PhysicsHooksData = &'static OneWayPlatform;
+
+/// Drops the contacts of anything reaching a [OneWayPlatform] from below or from the side
+pub struct OneWayPlatformHooks;
+
+impl PhysicsHooksWithQuery<PhysicsHooksData> for OneWayPlatformHooks {
+    fn modify_solver_contacts(
+        &self,
+        context: ContactModificationContextView,
+        platforms: &Query<PhysicsHooksData>,
+    ) {
+        // Rapier checks the normal pointing out of the first collider of the pair
+        let allowed_normal = if platforms.contains(context.collider1()) {
+            Vector::y()
+        } else if platforms.contains(context.collider2()) {
+            -Vector::y()
+        } else {
+            return;
+        };
+        context.raw.update_as_oneway_platform(&allowed_normal, 0.3);
+    }
+}
+
+/// Thickness of a one-way platform, relative to the tile size
+const ONE_WAY_THICKNESS: f32 = 0.25;
+
+pub fn setup_level(mut commands: Commands, levels: Res<Levels>) {
+    let level = levels.current();
+    commands.insert_resource(EnemyZones(level.enemy_zones()));
+    for rect in level.merge(Tile::Solid, true) {
+        let (min, max) = level.bounds(rect);
+        spawn_block(&mut commands, min, max, Color::BLACK);
+    }
+    for rect in level.merge(Tile::OneWay, false) {
+        let (min, max) = level.bounds(rect);
+        let min = Vec2::new(min.x, max.y - level.tile_size * ONE_WAY_THICKNESS);
+        spawn_block(&mut commands, min, max, Color::DARK_GRAY)
+            .insert(OneWayPlatform { top: max.y })
+            .insert(ActiveHooks::MODIFY_SOLVER_CONTACTS);
+    }
+    // Invisible walls on both sides, reaching well above the level, keep everything in the world
+    let half_size = level.size() / 2.;
+    for side in [-1., 1.] {
+        commands
+            .spawn()
+            .insert(DespawnOnExit(GameState::Playing))
+            .insert(RigidBody::Fixed)
+            .insert(Collider::cuboid(level.tile_size / 2., half_size.y * 2.))
+            .insert_bundle(TransformBundle::from(Transform::from_xyz(
+                side * (half_size.x + level.tile_size / 2.),
+                half_size.y,
+                0.,
+            )));
+    }
+}
+
+fn spawn_block<'w, 's, 'a>(
+    commands: &'a mut Commands<'w, 's>,
+    min: Vec2,
+    max: Vec2,
+    color: Color,
+) -> bevy::ecs::system::EntityCommands<'w, 's, 'a> {
+    let shape = shapes::Rectangle {
+        extents: max - min,
+        ..default()
+    };
+    let center = (min + max) / 2.;
+    let mut block = commands.spawn();
+    block
+        .insert(DespawnOnExit(GameState::Playing))
+        .insert_bundle(GeometryBuilder::build_as(
+            &shape,
+            DrawMode::Fill(bevy_prototype_lyon::prelude::FillMode::color(color)),
+            default(),
+        ))
+        .insert(RigidBody::Fixed)
+        .insert(Collider::cuboid((max.x - min.x) / 2., (max.y - min.y) / 2.))
+        .insert_bundle(TransformBundle::from(Transform::from_translation(
+            center.extend(0.),
+        )));
+    block
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(rows: &[&str]) -> Level {
+        Level {
+            name: "Test".to_string(),
+            tile_size: 10.,
+            rows: rows.iter().map(|row| row.to_string()).collect(),
+            path: String::new(),
+            seed: None,
+        }
+    }
+
+    const VALID: [&str; 4] = ["EE..", "EE..", "P..C", "####"];
+
+    #[test]
+    fn accepts_a_valid_level() {
+        assert_eq!(level(&VALID).validate(), Ok(()));
+    }
+
+    #[test]
+    fn reports_rows_of_different_lengths() {
+        let errors = level(&["EE..", "EE.", "P..C", "#####"])
+            .validate()
+            .unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                "rows[1] is 3 tiles wide, the first row 4".to_string(),
+                "rows[3] is 5 tiles wide, the first row 4".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_a_missing_or_duplicate_core() {
+        let errors = level(&["EE..", "EE..", "P...", "####"])
+            .validate()
+            .unwrap_err();
+        assert_eq!(
+            errors,
+            vec!["the level must contain exactly one 'C' tile, got 0".to_string()]
+        );
+
+        let errors = level(&["EE..", "EEC.", "P..C", "####"])
+            .validate()
+            .unwrap_err();
+        assert_eq!(
+            errors,
+            vec!["the level must contain exactly one 'C' tile, got 2".to_string()]
+        );
+    }
+
+    #[test]
+    fn reports_a_missing_player_start() {
+        let errors = level(&["EE..", "EE..", "...C", "####"])
+            .validate()
+            .unwrap_err();
+        assert_eq!(
+            errors,
+            vec!["the level must contain exactly one 'P' tile, got 0".to_string()]
+        );
+    }
+
+    #[test]
+    fn reports_unknown_tiles() {
+        let errors = level(&["EE..", "EE?.", "P..C", "##x#"])
+            .validate()
+            .unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                "rows[1] contains the unknown tile '?'".to_string(),
+                "rows[3] contains the unknown tile 'x'".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn merges_tiles_into_few_rectangles() {
+        let level = level(&["#..#", "#...", "P..C", "####"]);
+        assert_eq!(
+            level.merge(Tile::Solid, false),
+            vec![
+                TileRect {
+                    column: 0,
+                    row: 0,
+                    width: 1,
+                    height: 1
+                },
+                TileRect {
+                    column: 3,
+                    row: 0,
+                    width: 1,
+                    height: 1
+                },
+                TileRect {
+                    column: 0,
+                    row: 1,
+                    width: 1,
+                    height: 1
+                },
+                TileRect {
+                    column: 0,
+                    row: 3,
+                    width: 4,
+                    height: 1
+                },
+            ]
+        );
+        assert_eq!(
+            level.merge(Tile::Solid, true)[0],
+            TileRect {
+                column: 0,
+                row: 0,
+                width: 1,
+                height: 2
+            }
+        );
+    }
+
+    #[test]
+    fn finds_the_tile_under_a_position() {
+        // 40 by 40 pixels, centered on the origin
+        let level = level(&VALID);
+        assert_eq!(level.tile_at(Vec2::new(-15., 15.)), Some((0, 0)));
+        assert_eq!(level.tile_at(Vec2::new(15., -15.)), Some((3, 3)));
+        assert_eq!(level.tile_at(Vec2::new(5., 5.)), Some((2, 1)));
+        assert_eq!(level.tile_at(Vec2::new(-25., 0.)), None);
+        assert_eq!(level.tile_at(Vec2::new(0., -25.)), None);
+        assert_eq!(level.tile_at(Vec2::new(25., 0.)), None);
+    }
+
+    #[test]
+    fn enemy_zones_cover_the_spawn_tiles() {
+        let zones = level(&VALID).enemy_zones();
+        assert_eq!(zones.len(), 1);
+        assert_eq!(zones[0].min, Vec2::new(-20., 0.));
+        assert_eq!(zones[0].max, Vec2::new(0., 20.));
+    }
+
+    #[test]
+    fn loaded_files_replace_the_embedded_ones_once() {
+        let file = |name: &str| Level {
+            name: name.to_string(),
+            ..level(&VALID)
+        };
+        let generated = Level {
+            seed: Some(7),
+            ..file("Random")
+        };
+        let mut levels = Levels {
+            levels: vec![file("Plains"), file("Towers"), generated],
+            selected: 2,
+        };
+
+        levels.replace_files(vec![file("Plains"), file("Towers")]);
+        levels.replace_files(vec![file("Plains"), file("Towers")]);
+        let names: Vec<&str> = levels
+            .levels
+            .iter()
+            .map(|level| level.name.as_str())
+            .collect();
+        assert_eq!(names, ["Plains", "Towers", "Random"]);
+        assert_eq!(levels.selected, 0);
+
+        levels.replace_files(Vec::new());
+        assert_eq!(levels.levels.len(), 3);
+    }
+}
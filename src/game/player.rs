@@ -1,35 +1,129 @@
 use bevy::prelude::*;
 use bevy_prototype_lyon::prelude::*;
 use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
 
+use super::attack::PlayerAttack;
+use super::balance::Balance;
+use super::health::{PlayerHealth, Respawning};
+use super::level::{Levels, OneWayPlatform};
+use super::schedule::{InterpolatedTransform, FIXED_TIMESTEP, GRAVITY, PIXELS_PER_METER};
 use crate::actions::Actions;
+use crate::cleanup::DespawnOnExit;
+use crate::GameState;
 
-#[derive(Component)]
-pub struct Player {
-    jump_power: f32,
+/// How long after walking off a ledge the player can still jump, in seconds
+const COYOTE_TIME: f32 = 0.1;
+/// How long a jump pressed before landing is remembered, in seconds
+const JUMP_BUFFER: f32 = 0.12;
+/// How far below the player's feet something still counts as ground, in pixels
+const GROUND_PROBE: f32 = 4.;
+
+const CAPSULE_HALF_HEIGHT: f32 = 5.;
+pub(super) const CAPSULE_RADIUS: f32 = 20.;
+
+/// How the player runs and jumps, part of the [Balance] file.
+/// The movement reads it every gameplay tick, so changing the [Balance] resource while playing
+/// takes effect right away.
+#[derive(Clone, Debug, Deserialize)]
+pub struct PlayerMovementConfig {
+    /// Horizontal speed reached when the input is fully tilted, in pixels per second
+    pub max_speed: f32,
+    /// Horizontal speed gained per second while moving, in pixels per second squared
+    pub acceleration: f32,
+    /// Horizontal speed lost per second without input, in pixels per second squared
+    pub deceleration: f32,
+    /// Multiplies `acceleration` and `deceleration` while the player is in the air
+    pub air_control: f32,
+    /// Upward impulse of a jump
+    pub jump_impulse: f32,
+    /// Multiplies the upward speed once when the jump button is released early,
+    /// 1 gives jumps of a single height
+    pub jump_cut: f32,
+    /// Gravity scale of the player's body while it rises
+    pub gravity_scale: f32,
+    /// Multiplies `gravity_scale` while the player falls
+    pub fall_gravity_multiplier: f32,
+    /// Fastest falling speed, in pixels per second
+    pub terminal_velocity: f32,
 }
 
-impl Default for Player {
-    fn default() -> Self {
-        Self { jump_power: 100. }
+/// Where a jump with the button held goes, worked out from the movement config and the player's
+/// collider. It ignores the fixed timestep, so real jumps end up a few pixels shorter.
+#[derive(Clone, Copy, Debug)]
+pub struct JumpArc {
+    /// Upward speed right after the jump impulse, in pixels per second
+    launch_speed: f32,
+    rise_gravity: f32,
+    fall_gravity: f32,
+    run_speed: f32,
+}
+
+impl JumpArc {
+    pub fn new(config: &PlayerMovementConfig) -> Self {
+        // Rapier gives the capsule a density of 1 per square meter
+        let area = std::f32::consts::PI * CAPSULE_RADIUS * CAPSULE_RADIUS
+            + 4. * CAPSULE_RADIUS * CAPSULE_HALF_HEIGHT;
+        let mass = area / (PIXELS_PER_METER * PIXELS_PER_METER);
+        let rise_gravity = GRAVITY * config.gravity_scale;
+        Self {
+            launch_speed: config.jump_impulse / mass,
+            rise_gravity,
+            fall_gravity: rise_gravity * config.fall_gravity_multiplier,
+            run_speed: config.max_speed,
+        }
     }
+
+    /// Highest point above the take-off, in pixels
+    pub fn height(&self) -> f32 {
+        self.launch_speed * self.launch_speed / (2. * self.rise_gravity)
+    }
+
+    /// Horizontal distance covered at full speed before coming down to `rise` pixels above
+    /// the take-off, `None` when the jump does not get that high
+    pub fn reach(&self, rise: f32) -> Option<f32> {
+        let fall = self.height() - rise;
+        if fall < 0. {
+            return None;
+        }
+        let airtime =
+            self.launch_speed / self.rise_gravity + (2. * fall / self.fall_gravity).sqrt();
+        Some(self.run_speed * airtime)
+    }
+}
+
+#[derive(Component, Default)]
+pub struct Player {
+    /// Standing on something during the last gameplay tick
+    pub grounded: bool,
+    /// Time left to jump since the player was last grounded
+    coyote_timer: f32,
+    /// Time left for a buffered jump press to trigger on landing
+    jump_buffer_timer: f32,
+    /// Rising from a jump that can still be cut short
+    jumping: bool,
 }
 
-pub fn setup_player(mut commands: Commands) {
+pub fn setup_player(mut commands: Commands, balance: Res<Balance>, levels: Res<Levels>) {
+    let start =
+        levels.current().player_start() + Vec2::new(0., CAPSULE_HALF_HEIGHT + CAPSULE_RADIUS);
     let shape = shapes::Rectangle {
         extents: Vec2::new(40., 50.),
         origin: default(),
     };
     commands
         .spawn()
+        .insert(DespawnOnExit(GameState::Playing))
         .insert(Player::default())
+        .insert(PlayerAttack::default())
+        .insert(PlayerHealth::new(balance.health.max_hp))
         .insert_bundle(GeometryBuilder::build_as(
             &shape,
             DrawMode::Fill(bevy_prototype_lyon::prelude::FillMode::color(Color::CYAN)),
             Transform::default(),
         ))
         .insert(RigidBody::Dynamic)
-        .insert(Collider::capsule_y(5., 20.))
+        .insert(Collider::capsule_y(CAPSULE_HALF_HEIGHT, CAPSULE_RADIUS))
         .insert(LockedAxes::ROTATION_LOCKED)
         .insert(Velocity::default())
         .insert(Restitution {
@@ -37,28 +131,114 @@ pub fn setup_player(mut commands: Commands) {
             ..default()
         })
         .insert(ExternalImpulse::default())
-        .insert(GravityScale(10.))
-        .insert_bundle(TransformBundle::from(Transform::from_xyz(0.0, 200.0, 0.0)));
+        .insert(GravityScale(balance.player.gravity_scale))
+        .insert(InterpolatedTransform::default())
+        .insert_bundle(TransformBundle::from(Transform::from_translation(
+            start.extend(0.),
+        )));
+}
+
+/// Casts a slightly narrower capsule down from the player, so walls touching its sides
+/// are not mistaken for ground. One-way platforms only count once the feet are above them.
+fn is_grounded(
+    rapier_context: &RapierContext,
+    entity: Entity,
+    transform: &Transform,
+    platforms: &Query<&OneWayPlatform>,
+) -> bool {
+    let probe = Collider::capsule_y(CAPSULE_HALF_HEIGHT, CAPSULE_RADIUS - 2.);
+    let feet = transform.translation.y - CAPSULE_HALF_HEIGHT - CAPSULE_RADIUS;
+    let below_feet = |other| {
+        platforms
+            .get(other)
+            .map_or(true, |platform| feet >= platform.top - GROUND_PROBE)
+    };
+    rapier_context
+        .cast_shape(
+            transform.translation.truncate(),
+            0.,
+            Vec2::new(0., -(GROUND_PROBE + 2.)),
+            &probe,
+            1.,
+            QueryFilter::default()
+                .exclude_rigid_body(entity)
+                .exclude_sensors()
+                .predicate(&below_feet),
+        )
+        .is_some()
 }
 
+#[allow(clippy::type_complexity)]
 pub fn move_player_system(
-    actions: Res<Actions>,
-    mut players: Query<(&mut Velocity, &mut ExternalImpulse, &Player)>,
+    mut actions: ResMut<Actions>,
+    balance: Res<Balance>,
+    rapier_context: Res<RapierContext>,
+    platforms: Query<&OneWayPlatform>,
+    mut players: Query<
+        (
+            Entity,
+            &Transform,
+            &mut Velocity,
+            &mut ExternalImpulse,
+            &mut GravityScale,
+            &mut Player,
+        ),
+        Without<Respawning>,
+    >,
 ) {
-    for (mut velocity, mut impulse, player) in players.iter_mut() {
-        if actions.player_movement.is_none() {
-            velocity.linvel = Vec2::new(0., velocity.linvel.y);
+    let config = &balance.player;
+    let jump_pressed = actions.player_jump.take() == Some(true);
+    for (entity, transform, mut velocity, mut impulse, mut gravity, mut player) in
+        players.iter_mut()
+    {
+        // Still rising from a jump, the ground below does not count yet
+        player.grounded =
+            velocity.linvel.y <= 1. && is_grounded(&rapier_context, entity, transform, &platforms);
+        if player.grounded {
+            player.coyote_timer = COYOTE_TIME;
         } else {
-            velocity.linvel =
-                Vec2::new(actions.player_movement.unwrap().x * 100., velocity.linvel.y);
+            player.coyote_timer = (player.coyote_timer - FIXED_TIMESTEP).max(0.);
+        }
+        if jump_pressed {
+            player.jump_buffer_timer = JUMP_BUFFER;
+        } else {
+            player.jump_buffer_timer = (player.jump_buffer_timer - FIXED_TIMESTEP).max(0.);
         }
 
-        match actions.player_jump {
-            Some(true) => {
-                velocity.linvel = Vec2::new(velocity.linvel.x, 0.);
-                impulse.impulse = Vec2::new(0., player.jump_power);
+        let target_speed =
+            actions.player_movement.map_or(0., |movement| movement.x) * config.max_speed;
+        let mut acceleration = if target_speed != 0. {
+            config.acceleration
+        } else {
+            config.deceleration
+        };
+        if !player.grounded {
+            acceleration *= config.air_control;
+        }
+        let max_change = acceleration * FIXED_TIMESTEP;
+        velocity.linvel.x += (target_speed - velocity.linvel.x).clamp(-max_change, max_change);
+
+        if player.jumping && (velocity.linvel.y <= 0. || !actions.player_jump_held) {
+            if velocity.linvel.y > 0. {
+                velocity.linvel.y *= config.jump_cut;
             }
-            _ => {}
+            player.jumping = false;
+        }
+
+        if player.jump_buffer_timer > 0. && player.coyote_timer > 0. {
+            velocity.linvel.y = 0.;
+            impulse.impulse = Vec2::new(0., config.jump_impulse);
+            player.grounded = false;
+            player.jumping = true;
+            player.coyote_timer = 0.;
+            player.jump_buffer_timer = 0.;
         }
+
+        gravity.0 = if velocity.linvel.y < 0. {
+            config.gravity_scale * config.fall_gravity_multiplier
+        } else {
+            config.gravity_scale
+        };
+        velocity.linvel.y = velocity.linvel.y.max(-config.terminal_velocity);
     }
 }
@@ -0,0 +1,49 @@
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// The seed of the current run. Insert it before adding the game plugins to replay a run,
+/// otherwise a random one is picked whenever a run starts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GameSeed(pub u64);
+
+impl Default for GameSeed {
+    fn default() -> Self {
+        Self(rand::random::<u32>().into())
+    }
+}
+
+/// All the gameplay randomness comes from here.
+/// Every subsystem has its own stream derived from the seed, so a change in how often one of them
+/// draws numbers (or in system ordering) does not shift the numbers seen by the others.
+pub struct GameRng {
+    seed: u64,
+    pub spawning: StdRng,
+    pub drops: StdRng,
+    pub energy_scatter: StdRng,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            spawning: stream(seed, 0),
+            drops: stream(seed, 1),
+            energy_scatter: stream(seed, 2),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+fn stream(seed: u64, index: u64) -> StdRng {
+    StdRng::seed_from_u64(seed ^ index.wrapping_mul(0x9E37_79B9_7F4A_7C15))
+}
+
+impl FromWorld for GameRng {
+    fn from_world(world: &mut World) -> Self {
+        Self::new(world.resource::<GameSeed>().0)
+    }
+}
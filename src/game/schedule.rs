@@ -0,0 +1,174 @@
+use super::level::{OneWayPlatformHooks, PhysicsHooksData};
+use crate::GameState;
+
+use bevy::ecs::event::Events;
+use bevy::prelude::*;
+use bevy::time::{FixedTimestep, FixedTimesteps};
+use bevy::transform::TransformSystem;
+use bevy_rapier2d::prelude::*;
+
+/// Length of one gameplay tick in seconds.
+/// Gameplay and physics always advance by exactly this much, whatever the framerate is.
+pub const FIXED_TIMESTEP: f32 = 1. / 60.;
+
+const FIXED_TIMESTEP_LABEL: &str = "gameplay";
+
+/// Scale between Rapier's meters and the pixels the gameplay works in
+pub const PIXELS_PER_METER: f32 = 100.;
+/// Downward acceleration of bodies with a gravity scale of 1, in pixels per second squared
+pub const GRAVITY: f32 = 98.1;
+
+/// Runs once per gameplay tick, right after [CoreStage::Update].
+/// It contains the gameplay systems followed by the Rapier step.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, StageLabel)]
+pub struct FixedUpdateStage;
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, StageLabel)]
+enum FixedStages {
+    Gameplay,
+    PostPhysics,
+}
+
+/// This plugin sets up Rapier and the fixed timestep schedule the gameplay runs in.
+/// Systems added with [FixedStepAppExt::add_fixed_system_set] may read `GameState`
+/// but must not change it: transitions are driven from [CoreStage::Update].
+pub struct FixedStepPlugin;
+
+impl Plugin for FixedStepPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(RapierConfiguration {
+            timestep_mode: TimestepMode::Fixed {
+                dt: FIXED_TIMESTEP,
+                substeps: 1,
+            },
+            gravity: Vec2::new(0., -GRAVITY),
+            ..default()
+        })
+        .insert_resource(PhysicsHooksWithQueryResource::<PhysicsHooksData>(Box::new(
+            OneWayPlatformHooks,
+        )))
+        .add_plugin(
+            RapierPhysicsPlugin::<PhysicsHooksData>::pixels_per_meter(PIXELS_PER_METER)
+                .with_default_system_setup(false),
+        );
+
+        let fixed_schedule = Schedule::default()
+            .with_run_criteria(
+                FixedTimestep::step(FIXED_TIMESTEP as f64).with_label(FIXED_TIMESTEP_LABEL),
+            )
+            .with_stage(
+                FixedStages::Gameplay,
+                SystemStage::parallel().with_system_set(State::<GameState>::get_driver()),
+            )
+            .with_stage(
+                PhysicsStages::SyncBackend,
+                SystemStage::parallel().with_system_set(
+                    RapierPhysicsPlugin::<PhysicsHooksData>::get_systems(
+                        PhysicsStages::SyncBackend,
+                    ),
+                ),
+            )
+            .with_stage(
+                PhysicsStages::StepSimulation,
+                SystemStage::parallel().with_system_set(
+                    RapierPhysicsPlugin::<PhysicsHooksData>::get_systems(
+                        PhysicsStages::StepSimulation,
+                    ),
+                ),
+            )
+            .with_stage(
+                PhysicsStages::Writeback,
+                SystemStage::parallel().with_system_set(
+                    RapierPhysicsPlugin::<PhysicsHooksData>::get_systems(PhysicsStages::Writeback),
+                ),
+            )
+            .with_stage(
+                FixedStages::PostPhysics,
+                SystemStage::parallel()
+                    .with_system(record_physics_transforms)
+                    // Rapier does not clear its events, readers get them during the next tick
+                    .with_system(Events::<CollisionEvent>::update_system)
+                    .with_system(Events::<ContactForceEvent>::update_system),
+            );
+
+        app.add_stage_after(CoreStage::Update, FixedUpdateStage, fixed_schedule)
+            .add_stage_before(
+                CoreStage::Last,
+                PhysicsStages::DetectDespawn,
+                SystemStage::parallel().with_system_set(
+                    RapierPhysicsPlugin::<PhysicsHooksData>::get_systems(
+                        PhysicsStages::DetectDespawn,
+                    ),
+                ),
+            )
+            .add_system_to_stage(CoreStage::PreUpdate, restore_physics_transforms)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                interpolate_transforms.before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+pub trait FixedStepAppExt {
+    /// Adds a system set running once per gameplay tick, before the physics step
+    fn add_fixed_system_set(&mut self, system_set: SystemSet) -> &mut Self;
+}
+
+impl FixedStepAppExt for App {
+    fn add_fixed_system_set(&mut self, system_set: SystemSet) -> &mut Self {
+        self.schedule
+            .stage(FixedUpdateStage, |schedule: &mut Schedule| {
+                schedule.add_system_set_to_stage(FixedStages::Gameplay, system_set)
+            });
+        self
+    }
+}
+
+/// Smooths the rendered [Transform] of a physics driven entity between the last two gameplay ticks.
+/// Between frames the [Transform] is put back to the simulated pose, so gameplay and Rapier
+/// never see the interpolated one.
+#[derive(Component, Default)]
+pub struct InterpolatedTransform {
+    previous: Option<Transform>,
+    current: Option<Transform>,
+}
+
+impl InterpolatedTransform {
+    /// Drops the smoothing from the last pose, for an entity teleported to `transform` instead
+    /// of moving there
+    pub fn snap(&mut self, transform: Transform) {
+        self.previous = Some(transform);
+        self.current = Some(transform);
+    }
+}
+
+fn record_physics_transforms(mut query: Query<(&Transform, &mut InterpolatedTransform)>) {
+    for (transform, mut interpolated) in query.iter_mut() {
+        interpolated.previous = interpolated.current.or(Some(*transform));
+        interpolated.current = Some(*transform);
+    }
+}
+
+fn restore_physics_transforms(mut query: Query<(&mut Transform, &InterpolatedTransform)>) {
+    for (mut transform, interpolated) in query.iter_mut() {
+        if let Some(current) = interpolated.current {
+            *transform = current;
+        }
+    }
+}
+
+pub fn interpolate_transforms(
+    mut query: Query<(&mut Transform, &InterpolatedTransform)>,
+    fixed_timesteps: Res<FixedTimesteps>,
+) {
+    let alpha = fixed_timesteps
+        .get(FIXED_TIMESTEP_LABEL)
+        .map_or(1., |state| state.overstep_percentage() as f32)
+        .clamp(0., 1.);
+    for (mut transform, interpolated) in query.iter_mut() {
+        if let (Some(previous), Some(current)) = (interpolated.previous, interpolated.current) {
+            transform.translation = previous.translation.lerp(current.translation, alpha);
+            transform.rotation = previous.rotation.slerp(current.rotation, alpha);
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use bevy::prelude::*;
+use bevy::utils::Duration;
+use bevy_prototype_lyon::prelude::*;
+
+use super::balance::Balance;
+use super::combo::Combo;
+use super::enemy::Enemy;
+use super::game::{hit_enemy, EnergyPoint};
+use super::health::Respawning;
+use super::player::Player;
+use super::rng::GameRng;
+use super::schedule::{InterpolatedTransform, FIXED_TIMESTEP};
+use crate::actions::Actions;
+use crate::audio::SoundEffect;
+use crate::cleanup::DespawnOnExit;
+use crate::GameState;
+
+const PROJECTILE_RADIUS: f32 = 4.;
+
+#[derive(Component)]
+pub struct Tower {
+    cooldown: Timer,
+}
+
+#[derive(Component)]
+pub struct Projectile {
+    velocity: Vec2,
+    /// Seconds left before the projectile disappears without hitting anything
+    lifetime: f32,
+}
+
+pub fn build_tower(
+    mut commands: Commands,
+    mut actions: ResMut<Actions>,
+    mut energy: ResMut<EnergyPoint>,
+    balance: Res<Balance>,
+    player: Query<&Transform, (With<Player>, Without<Respawning>)>,
+) {
+    if actions.build_tower.take() != Some(true) || energy.0 < balance.tower.cost {
+        return;
+    }
+    // Nowhere to build while the player is down
+    let transform = match player.get_single() {
+        Ok(player) => Transform::from_translation(player.translation),
+        Err(_) => return,
+    };
+    energy.0 -= balance.tower.cost;
+    let shape = shapes::Rectangle {
+        extents: Vec2::new(20., 40.),
+        origin: default(),
+    };
+    commands
+        .spawn()
+        .insert(DespawnOnExit(GameState::Playing))
+        .insert(Tower {
+            cooldown: Timer::from_seconds(balance.tower.fire_interval, false),
+        })
+        .insert_bundle(GeometryBuilder::build_as(
+            &shape,
+            DrawMode::Fill(bevy_prototype_lyon::prelude::FillMode::color(Color::GRAY)),
+            transform,
+        ));
+}
+
+pub fn fire_towers(
+    mut commands: Commands,
+    mut towers: Query<(&Transform, &mut Tower)>,
+    enemies: Query<&Transform, With<Enemy>>,
+    balance: Res<Balance>,
+) {
+    for (tower_transform, mut tower) in towers.iter_mut() {
+        tower.cooldown.tick(Duration::from_secs_f32(FIXED_TIMESTEP));
+        if !tower.cooldown.finished() {
+            continue;
+        }
+        let origin = tower_transform.translation.truncate();
+        let nearest = enemies
+            .iter()
+            .map(|transform| transform.translation.truncate() - origin)
+            .filter(|diff| diff.length() <= balance.tower.range)
+            .min_by(|a, b| a.length_squared().total_cmp(&b.length_squared()));
+        let diff = match nearest {
+            Some(diff) => diff,
+            // Stay loaded until an enemy comes in range
+            None => continue,
+        };
+        tower.cooldown.reset();
+        let shape = shapes::Circle {
+            radius: PROJECTILE_RADIUS,
+            ..default()
+        };
+        let transform = Transform::from_translation(origin.extend(1.));
+        commands
+            .spawn()
+            .insert(DespawnOnExit(GameState::Playing))
+            .insert(Projectile {
+                velocity: diff.normalize_or_zero() * balance.tower.projectile_speed,
+                lifetime: balance.tower.range / balance.tower.projectile_speed,
+            })
+            .insert(InterpolatedTransform::default())
+            .insert_bundle(GeometryBuilder::build_as(
+                &shape,
+                DrawMode::Fill(bevy_prototype_lyon::prelude::FillMode::color(Color::WHITE)),
+                transform,
+            ));
+    }
+}
+
+pub fn move_projectiles(
+    mut commands: Commands,
+    mut projectiles: Query<(Entity, &mut Transform, &mut Projectile), Without<Enemy>>,
+    mut enemies: Query<(Entity, &Transform, &mut Enemy)>,
+    balance: Res<Balance>,
+    mut sound_effects: EventWriter<SoundEffect>,
+    mut rng: ResMut<GameRng>,
+    mut combo: ResMut<Combo>,
+) {
+    for (entity, mut transform, mut projectile) in projectiles.iter_mut() {
+        transform.translation += (projectile.velocity * FIXED_TIMESTEP).extend(0.);
+        projectile.lifetime -= FIXED_TIMESTEP;
+        let position = transform.translation.truncate();
+        let hit = enemies.iter_mut().find(|(_, enemy_transform, enemy)| {
+            let reach = enemy.kind.archetype(&balance).radius + PROJECTILE_RADIUS;
+            enemy.hp > 0
+                && enemy_transform
+                    .translation
+                    .truncate()
+                    .distance_squared(position)
+                    < reach * reach
+        });
+        if let Some(enemy) = hit {
+            if hit_enemy(&mut commands, &balance, &mut rng, &mut combo, enemy) {
+                sound_effects.send(SoundEffect::Attack);
+            }
+            commands.entity(entity).despawn();
+        } else if projectile.lifetime <= 0. {
+            commands.entity(entity).despawn();
+        }
+    }
+}
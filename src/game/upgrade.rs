@@ -0,0 +1,131 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
+
+use super::balance::Balance;
+use super::game::{CollectArea, Core, EnergyPoint};
+use super::schedule::FIXED_TIMESTEP;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum UpgradeKind {
+    /// Adds `amount` to the core's max HP, and heals it by as much
+    MaxHp,
+    /// Adds `amount` HP per second to the core's regeneration
+    Regen,
+    /// Adds `amount` pixels to the radius energy gets collected from
+    CollectRadius,
+    /// Adds `amount` to the speed factor of collected energy flying to the core
+    HomingSpeed,
+    /// Adds `amount` pixels to the radius of the aura hurting enemies when the core is hit
+    ReflectAura,
+}
+
+impl UpgradeKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            UpgradeKind::MaxHp => "Core max HP",
+            UpgradeKind::Regen => "Core regen",
+            UpgradeKind::CollectRadius => "Collect radius",
+            UpgradeKind::HomingSpeed => "Energy homing",
+            UpgradeKind::ReflectAura => "Reflect aura",
+        }
+    }
+}
+
+/// One upgrade sold in the shop between waves
+#[derive(Clone, Debug, Deserialize)]
+pub struct UpgradeDefinition {
+    pub kind: UpgradeKind,
+    /// What every level adds, see [UpgradeKind]
+    pub amount: f32,
+    /// Energy paid for the first level
+    pub base_cost: i32,
+    /// Multiplies the cost after every level bought
+    pub cost_growth: f32,
+    pub max_level: u32,
+}
+
+impl UpgradeDefinition {
+    /// Energy paid to go from `level` to the next one
+    pub fn cost(&self, level: u32) -> i32 {
+        (self.base_cost as f32 * self.cost_growth.powi(level as i32)).round() as i32
+    }
+}
+
+/// Levels bought during the current run, in the order of [Balance::upgrades]
+pub struct Upgrades {
+    pub levels: Vec<u32>,
+}
+
+impl FromWorld for Upgrades {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            levels: vec![0; world.resource::<Balance>().upgrades.len()],
+        }
+    }
+}
+
+impl Upgrades {
+    /// The cost of the next level of the upgrade `index`, `None` once it is maxed out
+    pub fn next_cost(&self, balance: &Balance, index: usize) -> Option<i32> {
+        let definition = balance.upgrades.get(index)?;
+        let level = *self.levels.get(index)?;
+        (level < definition.max_level).then(|| definition.cost(level))
+    }
+}
+
+/// Buys the next level of the upgrade at this index of [Balance::upgrades]
+pub struct BuyUpgrade(pub usize);
+
+pub fn buy_upgrades(
+    mut buy_events: EventReader<BuyUpgrade>,
+    balance: Res<Balance>,
+    mut upgrades: ResMut<Upgrades>,
+    mut energy: ResMut<EnergyPoint>,
+    mut core: Query<&mut Core>,
+    mut collect_area: Query<(&mut CollectArea, &mut Collider)>,
+) {
+    for BuyUpgrade(index) in buy_events.iter() {
+        let cost = match upgrades.next_cost(&balance, *index) {
+            Some(cost) if cost <= energy.0 => cost,
+            _ => continue,
+        };
+        energy.0 -= cost;
+        upgrades.levels[*index] += 1;
+
+        let definition = &balance.upgrades[*index];
+        let mut core = core.single_mut();
+        let (mut area, mut collider) = collect_area.single_mut();
+        match definition.kind {
+            UpgradeKind::MaxHp => {
+                core.max_hp += definition.amount as i32;
+                core.hp += definition.amount as i32;
+            }
+            UpgradeKind::Regen => core.regen += definition.amount,
+            UpgradeKind::CollectRadius => {
+                area.radius += definition.amount;
+                *collider = Collider::ball(area.radius);
+            }
+            UpgradeKind::HomingSpeed => area.homing_speed += definition.amount,
+            UpgradeKind::ReflectAura => core.reflect_radius += definition.amount,
+        }
+        info!(
+            "Bought {} level {} for {cost} energy",
+            definition.kind.label(),
+            upgrades.levels[*index]
+        );
+    }
+}
+
+pub fn regenerate_core(mut core: Query<&mut Core>) {
+    for mut core in core.iter_mut() {
+        if core.hp >= core.max_hp || core.hp <= 0 {
+            core.regen_progress = 0.;
+            continue;
+        }
+        core.regen_progress += core.regen * FIXED_TIMESTEP;
+        let healed = core.regen_progress.floor();
+        core.regen_progress -= healed;
+        core.hp = (core.hp + healed as i32).min(core.max_hp);
+    }
+}
@@ -0,0 +1,100 @@
+use super::balance::{Balance, WaveDefinition};
+use super::enemy::Enemy;
+use super::schedule::FIXED_TIMESTEP;
+
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WavePhase {
+    /// Waiting for the next wave to start
+    Resting,
+    /// Enemies of the current wave are still being spawned
+    Spawning,
+    /// Everything is spawned, waiting for the last enemy to die
+    Fighting,
+}
+
+/// Sent once every enemy of a wave has been spawned and killed
+pub struct WaveCleared {
+    pub wave: u32,
+}
+
+pub struct Wave {
+    /// Starts at 1, 0 while resting before the first wave
+    pub number: u32,
+    pub phase: WavePhase,
+    pub definition: WaveDefinition,
+    pub enemy_count: u32,
+    pub spawned: u32,
+    pub spawn_timer: Timer,
+    rest_timer: Timer,
+}
+
+impl Wave {
+    fn new(balance: &Balance) -> Self {
+        Self {
+            number: 0,
+            phase: WavePhase::Resting,
+            definition: balance.wave(1),
+            enemy_count: 0,
+            spawned: 0,
+            spawn_timer: Timer::from_seconds(1.0, true),
+            rest_timer: Timer::from_seconds(balance.first_rest, false),
+        }
+    }
+
+    fn start_next(&mut self, balance: &Balance) {
+        self.number += 1;
+        self.phase = WavePhase::Spawning;
+        self.definition = balance.wave(self.number);
+        self.enemy_count = self.definition.count;
+        self.spawned = 0;
+        self.spawn_timer = Timer::from_seconds(self.definition.interval, true);
+    }
+
+    pub fn rest_remaining(&self) -> f32 {
+        self.rest_timer.duration().as_secs_f32() - self.rest_timer.elapsed_secs()
+    }
+}
+
+impl FromWorld for Wave {
+    fn from_world(world: &mut World) -> Self {
+        Self::new(world.resource::<Balance>())
+    }
+}
+
+pub fn update_wave(
+    mut wave: ResMut<Wave>,
+    balance: Res<Balance>,
+    enemies: Query<(), With<Enemy>>,
+    mut wave_cleared: EventWriter<WaveCleared>,
+) {
+    match wave.phase {
+        WavePhase::Resting => {
+            wave.rest_timer
+                .tick(Duration::from_secs_f32(FIXED_TIMESTEP));
+            if wave.rest_timer.finished() {
+                wave.start_next(&balance);
+            }
+        }
+        WavePhase::Spawning => {
+            if wave.spawned >= wave.enemy_count {
+                wave.phase = WavePhase::Fighting;
+            }
+        }
+        WavePhase::Fighting => {
+            if enemies.is_empty() {
+                wave_cleared.send(WaveCleared { wave: wave.number });
+                wave.phase = WavePhase::Resting;
+                wave.rest_timer = Timer::from_seconds(balance.rest, false);
+            }
+        }
+    }
+}
+
+pub fn log_wave_cleared(mut wave_cleared: EventReader<WaveCleared>) {
+    for event in wave_cleared.iter() {
+        info!("Wave {} cleared", event.wave);
+    }
+}
@@ -0,0 +1,109 @@
+use crate::cleanup::DespawnOnExit;
+use crate::game::{EnergyPoint, SurvivalTime, Wave};
+use crate::loading::FontAssets;
+use crate::menu::{spawn_button, ButtonColors};
+use crate::GameState;
+use bevy::prelude::*;
+
+pub struct GameOverPlugin;
+
+/// This plugin draws the results of the run during `GameState::GameOver`, which is pushed on top
+/// of the frozen `GameState::Playing`, and lets the player retry or go back to the menu
+impl Plugin for GameOverPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_enter(GameState::GameOver).with_system(setup_game_over))
+            .add_system_set(
+                SystemSet::on_update(GameState::GameOver).with_system(click_game_over_buttons),
+            );
+    }
+}
+
+#[derive(Component, Clone, Copy)]
+enum GameOverButton {
+    Retry,
+    MainMenu,
+}
+
+impl GameOverButton {
+    fn label(self) -> &'static str {
+        match self {
+            GameOverButton::Retry => "Retry",
+            GameOverButton::MainMenu => "Main menu",
+        }
+    }
+
+    fn next_state(self) -> GameState {
+        match self {
+            GameOverButton::Retry => GameState::Playing,
+            GameOverButton::MainMenu => GameState::Menu,
+        }
+    }
+}
+
+fn setup_game_over(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    button_colors: Res<ButtonColors>,
+    energy: Res<EnergyPoint>,
+    wave: Res<Wave>,
+    survival_time: Res<SurvivalTime>,
+) {
+    let text_style = |font_size| TextStyle {
+        font: font_assets.fira_sans.clone(),
+        font_size,
+        color: Color::rgb(0.9, 0.9, 0.9),
+    };
+    let seconds = survival_time.0 as u32;
+    let results = [
+        format!("Energy: {}", energy.0),
+        format!("Wave reached: {}", wave.number),
+        format!("Time survived: {}:{:02}", seconds / 60, seconds % 60),
+    ];
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+            ..Default::default()
+        })
+        .insert(DespawnOnExit(GameState::GameOver))
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle::from_section("Game Over", text_style(60.0)));
+            for line in results {
+                parent.spawn_bundle(TextBundle::from_section(line, text_style(30.0)));
+            }
+            for button in [GameOverButton::Retry, GameOverButton::MainMenu] {
+                spawn_button(parent, &font_assets, &button_colors, button.label(), button);
+            }
+        });
+}
+
+#[allow(clippy::type_complexity)]
+fn click_game_over_buttons(
+    button_colors: Res<ButtonColors>,
+    mut state: ResMut<State<GameState>>,
+    mut interaction_query: Query<
+        (&Interaction, &mut UiColor, &GameOverButton),
+        (Changed<Interaction>, With<Button>),
+    >,
+) {
+    for (interaction, mut color, button) in &mut interaction_query {
+        match *interaction {
+            Interaction::Clicked => {
+                // Replacing the whole stack also exits the paused `Playing` below
+                state.replace(button.next_state()).unwrap();
+            }
+            Interaction::Hovered => {
+                *color = button_colors.hovered;
+            }
+            Interaction::None => {
+                *color = button_colors.normal;
+            }
+        }
+    }
+}
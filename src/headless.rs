@@ -0,0 +1,97 @@
+use crate::actions::ActionsPlugin;
+use crate::cleanup::CleanupPlugin;
+use crate::display::ViewArea;
+use crate::game::{GameSeed, MainGamePlugin};
+use crate::GameState;
+
+use bevy::app::App;
+use bevy::hierarchy::HierarchyPlugin;
+use bevy::input::InputPlugin;
+use bevy::prelude::*;
+use bevy::time::{FixedTimesteps, TimePlugin};
+use bevy::transform::TransformPlugin;
+use bevy::utils::Duration;
+
+/// This plugin runs the gameplay without window, renderer or audio backend.
+/// The game starts directly in `GameState::Playing`, skipping loading and menu.
+/// Sound effects are still sent as events, there is just nobody playing them.
+pub struct HeadlessGamePlugin;
+
+impl Plugin for HeadlessGamePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_state(GameState::Playing)
+            // Set by the DisplayPlugin otherwise, the camera systems read it
+            .init_resource::<ViewArea>()
+            .add_plugin(CleanupPlugin)
+            .add_plugin(ActionsPlugin)
+            .add_plugin(MainGamePlugin);
+    }
+}
+
+/// A headless [App] built on `MinimalPlugins` whose clock only advances when
+/// [HeadlessGame::step] is called, so a run does not depend on the speed of the machine.
+pub struct HeadlessGame {
+    pub app: App,
+}
+
+impl HeadlessGame {
+    pub fn new() -> Self {
+        Self::build(None)
+    }
+
+    /// Same as [HeadlessGame::new], but every run with the same seed plays out the same
+    pub fn with_seed(seed: u64) -> Self {
+        Self::build(Some(GameSeed(seed)))
+    }
+
+    fn build(seed: Option<GameSeed>) -> Self {
+        let mut app = App::new();
+        // Only a seed there before the game plugins is kept from one run to the next
+        if let Some(seed) = seed {
+            app.insert_resource(seed);
+        }
+        app.add_plugins_with(MinimalPlugins, |group| group.disable::<TimePlugin>())
+            .add_plugin(TransformPlugin)
+            .add_plugin(HierarchyPlugin)
+            .add_plugin(InputPlugin)
+            .init_resource::<Time>()
+            .init_resource::<FixedTimesteps>()
+            .add_plugin(HeadlessGamePlugin);
+
+        // Without a last update the first step would have a zero delta
+        let mut time = app.world.resource_mut::<Time>();
+        let startup = time.startup();
+        time.update_with_instant(startup);
+
+        Self { app }
+    }
+
+    /// Advances the clock by `delta` and runs one frame
+    pub fn step(&mut self, delta: Duration) {
+        let mut time = self.app.world.resource_mut::<Time>();
+        let last_update = time.last_update().unwrap_or_else(|| time.startup());
+        time.update_with_instant(last_update + delta);
+        self.app.update();
+    }
+
+    /// Runs `frames` frames of `delta` each
+    pub fn run_frames(&mut self, frames: usize, delta: Duration) {
+        for _ in 0..frames {
+            self.step(delta);
+        }
+    }
+
+    pub fn world(&self) -> &World {
+        &self.app.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.app.world
+    }
+}
+
+impl Default for HeadlessGame {
+    fn default() -> Self {
+        Self::new()
+    }
+}
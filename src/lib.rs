@@ -1,33 +1,92 @@
 mod actions;
 mod audio;
-pub mod constants;
+mod cleanup;
+mod display;
+mod editor;
 mod game;
+mod gameover;
+mod headless;
 mod loading;
 mod menu;
+mod pause;
+mod settings;
+mod shop;
+mod storage;
+mod touch;
 
+pub use crate::actions::Actions;
 use crate::actions::ActionsPlugin;
 use crate::audio::InternalAudioPlugin;
-use crate::game::MainGamePlugin;
+use crate::cleanup::CleanupPlugin;
+use crate::display::DisplayPlugin;
+pub use crate::display::DisplaySettings;
+use crate::editor::EditorPlugin;
+pub use crate::game::{
+    check_reachable, procedural_level, Balance, BalanceLoader, Core, EnergyPoint, GameRng,
+    GameSeed, JumpArc, Level, LevelLoader, Levels, Player, Surface, Wave,
+};
+use crate::game::{HudPlugin, MainGamePlugin};
+use crate::gameover::GameOverPlugin;
+pub use crate::headless::{HeadlessGame, HeadlessGamePlugin};
 use crate::loading::LoadingPlugin;
 use crate::menu::MenuPlugin;
+use crate::pause::PausePlugin;
+use crate::settings::SettingsPlugin;
+use crate::shop::ShopPlugin;
+use crate::touch::TouchControlsPlugin;
 
 use bevy::app::App;
 #[cfg(debug_assertions)]
 use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
 use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::ShapePlugin;
+#[cfg(debug_assertions)]
+use bevy_rapier2d::render::RapierDebugRenderPlugin;
 
 // This example game uses States to separate logic
 // See https://bevy-cheatbook.github.io/programming/states.html
 // Or https://github.com/bevyengine/bevy/blob/main/examples/ecs/state.rs
 #[derive(Clone, Eq, PartialEq, Debug, Hash)]
-enum GameState {
+pub enum GameState {
     // During the loading State the LoadingPlugin will load our assets
     Loading,
     // During this State the actual game logic is executed
     Playing,
+    // The run is over, its results are shown until the player retries or goes back to the menu
     GameOver,
     // Here the menu is drawn and waiting for player interaction
     Menu,
+    // Pushed on top of Playing, which stays frozen underneath
+    Paused,
+    // Pushed on top of the screen it was opened from
+    Settings,
+    // Pushed on top of Playing after every cleared wave
+    Shop,
+    // Levels are painted here, and played from here to test them
+    Editor,
+}
+
+impl GameState {
+    /// Every state, so per state systems can be registered in a loop
+    pub const ALL: [GameState; 8] = [
+        GameState::Loading,
+        GameState::Playing,
+        GameState::GameOver,
+        GameState::Menu,
+        GameState::Paused,
+        GameState::Settings,
+        GameState::Shop,
+        GameState::Editor,
+    ];
+}
+
+/// Systems leaving a running game for another screen, ordered so that the most important one
+/// wins: only the first transition queued during a frame is applied, the others fail.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, SystemLabel)]
+enum RunTransition {
+    GameOver,
+    Pause,
+    Shop,
 }
 
 pub struct GamePlugin;
@@ -35,17 +94,28 @@ pub struct GamePlugin;
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
         app.add_state(GameState::Loading)
+            .add_plugin(CleanupPlugin)
             .add_plugin(LoadingPlugin)
             .add_plugin(MenuPlugin)
+            .add_plugin(GameOverPlugin)
+            .add_plugin(PausePlugin)
+            .add_plugin(SettingsPlugin)
+            .add_plugin(ShopPlugin)
+            .add_plugin(EditorPlugin)
             .add_plugin(ActionsPlugin)
+            .add_plugin(TouchControlsPlugin)
             .add_plugin(InternalAudioPlugin)
+            .add_plugin(ShapePlugin)
             // .add_plugin(PlayerPlugin)
-            .add_plugin(MainGamePlugin);
+            .add_plugin(MainGamePlugin)
+            .add_plugin(HudPlugin)
+            .add_plugin(DisplayPlugin);
 
         #[cfg(debug_assertions)]
         {
-            app.add_plugin(FrameTimeDiagnosticsPlugin::default())
-                .add_plugin(LogDiagnosticsPlugin::default());
+            app.add_plugin(FrameTimeDiagnosticsPlugin)
+                .add_plugin(LogDiagnosticsPlugin::default())
+                .add_plugin(RapierDebugRenderPlugin::default());
         }
     }
 }
@@ -1,3 +1,4 @@
+use crate::game::{shipped_levels, Balance, BalanceLoader, Level, LevelLoader, Levels};
 use crate::GameState;
 use bevy::prelude::*;
 use bevy_asset_loader::prelude::*;
@@ -10,13 +11,24 @@ pub struct LoadingPlugin;
 /// If interested, take a look at https://bevy-cheatbook.github.io/features/assets.html
 impl Plugin for LoadingPlugin {
     fn build(&self, app: &mut App) {
-        app.add_loading_state(
-            LoadingState::new(GameState::Loading)
-                .with_collection::<FontAssets>()
-                .with_collection::<AudioAssets>()
-                .with_collection::<TextureAssets>()
-                .continue_to_state(GameState::Menu),
-        );
+        app.add_asset::<Balance>()
+            .init_asset_loader::<BalanceLoader>()
+            .add_asset::<Level>()
+            .init_asset_loader::<LevelLoader>()
+            .add_loading_state(
+                LoadingState::new(GameState::Loading)
+                    .with_collection::<FontAssets>()
+                    .with_collection::<AudioAssets>()
+                    .with_collection::<TextureAssets>()
+                    .with_collection::<BalanceAssets>()
+                    .with_collection::<LevelAssets>()
+                    .continue_to_state(GameState::Menu),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Loading)
+                    .with_system(insert_balance)
+                    .with_system(insert_levels),
+            );
     }
 }
 
@@ -41,6 +53,52 @@ pub struct AudioAssets {
 
 #[derive(AssetCollection)]
 pub struct TextureAssets {
+    #[allow(dead_code)]
     #[asset(path = "textures/bevy.png")]
     pub texture_bevy: Handle<Image>,
 }
+
+/// The balance file is only read here: gameplay uses the [Balance] resource,
+/// which also exists when running without an asset server
+#[derive(AssetCollection)]
+pub struct BalanceAssets {
+    #[asset(path = "balance/default.balance.ron")]
+    pub balance: Handle<Balance>,
+}
+
+fn insert_balance(
+    mut commands: Commands,
+    balance_assets: Res<BalanceAssets>,
+    balances: Res<Assets<Balance>>,
+) {
+    if let Some(balance) = balances.get(&balance_assets.balance) {
+        commands.insert_resource(balance.clone());
+    }
+}
+
+macro_rules! level_assets {
+    ($($path:literal),*) => {
+        /// Listed in the order the menu offers them
+        #[derive(AssetCollection)]
+        pub struct LevelAssets {
+            #[asset(paths($($path),*), collection(typed))]
+            pub levels: Vec<Handle<Level>>,
+        }
+    };
+}
+shipped_levels!(level_assets);
+
+/// Swaps the embedded level files for the loaded ones
+fn insert_levels(
+    level_assets: Res<LevelAssets>,
+    assets: Res<Assets<Level>>,
+    mut levels: ResMut<Levels>,
+) {
+    levels.replace_files(
+        level_assets
+            .levels
+            .iter()
+            .filter_map(|handle| assets.get(handle).cloned())
+            .collect(),
+    );
+}
@@ -1,22 +1,30 @@
 // disable console on windows for release builds
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use bevy::prelude::{App, ClearColor, Color, Msaa, WindowDescriptor};
+use bevy::prelude::{App, ClearColor, Color, Msaa};
 use bevy::DefaultPlugins;
-use td_platformer::GamePlugin;
-use td_platformer::constants::{WIN_WIDTH, WIN_HEIGHT};
+use td_platformer::{DisplaySettings, GamePlugin, GameSeed};
 
 fn main() {
-    App::new()
-        .insert_resource(Msaa { samples: 1 })
+    let display = DisplaySettings::load();
+    let mut app = App::new();
+    app.insert_resource(Msaa { samples: 1 })
         .insert_resource(ClearColor(Color::rgb(0.4, 0.4, 0.4)))
-        .insert_resource(WindowDescriptor {
-            width: WIN_WIDTH,
-            height: WIN_HEIGHT,
-            title: "TD Platformer".to_string(),
-            ..Default::default()
-        })
-        .add_plugins(DefaultPlugins)
-        .add_plugin(GamePlugin)
-        .run();
+        .insert_resource(display.window_descriptor("TD Platformer"))
+        .insert_resource(display);
+    // `--seed <number>` replays a run
+    if let Some(seed) = seed_from_args() {
+        app.insert_resource(seed);
+    }
+    app.add_plugins(DefaultPlugins).add_plugin(GamePlugin).run();
+}
+
+fn seed_from_args() -> Option<GameSeed> {
+    let mut args = std::env::args();
+    while let Some(arg) = args.next() {
+        if arg == "--seed" {
+            return args.next()?.parse().ok().map(GameSeed);
+        }
+    }
+    None
 }
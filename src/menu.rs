@@ -1,23 +1,29 @@
+use crate::cleanup::DespawnOnExit;
+use crate::game::Levels;
 use crate::loading::FontAssets;
 use crate::GameState;
 use bevy::prelude::*;
 
 pub struct MenuPlugin;
 
-/// This plugin is responsible for the game menu (containing only one button...)
+/// This plugin is responsible for the game menu, leading to the game and the settings.
+/// The level played is picked here too
 /// The menu is only drawn during the State `GameState::Menu` and is removed when that state is exited
 impl Plugin for MenuPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<ButtonColors>()
             .add_system_set(SystemSet::on_enter(GameState::Menu).with_system(setup_menu))
-            .add_system_set(SystemSet::on_update(GameState::Menu).with_system(click_play_button))
-            .add_system_set(SystemSet::on_exit(GameState::Menu).with_system(cleanup_menu));
+            .add_system_set(
+                SystemSet::on_update(GameState::Menu)
+                    .with_system(click_menu_buttons)
+                    .with_system(update_level_text),
+            );
     }
 }
 
-struct ButtonColors {
-    normal: UiColor,
-    hovered: UiColor,
+pub struct ButtonColors {
+    pub normal: UiColor,
+    pub hovered: UiColor,
 }
 
 impl Default for ButtonColors {
@@ -29,55 +35,156 @@ impl Default for ButtonColors {
     }
 }
 
+/// Adds a button of the same size and colors as the menu ones under `parent`
+pub fn spawn_button(
+    parent: &mut ChildBuilder,
+    font_assets: &FontAssets,
+    button_colors: &ButtonColors,
+    label: &str,
+    marker: impl Component,
+) {
+    spawn_sized_button(
+        parent,
+        font_assets,
+        button_colors,
+        label,
+        Size::new(Val::Px(200.0), Val::Px(50.0)),
+        40.0,
+        marker,
+    );
+}
+
+/// Like [spawn_button], for buttons smaller than the menu ones
+pub fn spawn_sized_button(
+    parent: &mut ChildBuilder,
+    font_assets: &FontAssets,
+    button_colors: &ButtonColors,
+    label: &str,
+    size: Size<Val>,
+    font_size: f32,
+    marker: impl Component,
+) {
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size,
+                margin: UiRect::all(Val::Px(font_size / 4.)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: button_colors.normal,
+            ..Default::default()
+        })
+        .insert(marker)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle::from_section(
+                label,
+                TextStyle {
+                    font: font_assets.fira_sans.clone(),
+                    font_size,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                },
+            ));
+        });
+}
+
+#[derive(Component, Clone, Copy)]
+enum MenuButton {
+    Play,
+    NextLevel,
+    Editor,
+    Settings,
+}
+
+#[derive(Component)]
+struct LevelText;
+
 fn setup_menu(
     mut commands: Commands,
     font_assets: Res<FontAssets>,
     button_colors: Res<ButtonColors>,
 ) {
-    commands.spawn_bundle(Camera2dBundle::default());
     commands
-        .spawn_bundle(ButtonBundle {
+        .spawn_bundle(NodeBundle {
             style: Style {
-                size: Size::new(Val::Px(120.0), Val::Px(50.0)),
-                margin: UiRect::all(Val::Auto),
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
                 justify_content: JustifyContent::Center,
                 align_items: AlignItems::Center,
                 ..Default::default()
             },
-            color: button_colors.normal,
+            color: Color::NONE.into(),
             ..Default::default()
         })
+        .insert(DespawnOnExit(GameState::Menu))
         .with_children(|parent| {
-            parent.spawn_bundle(TextBundle {
-                text: Text {
-                    sections: vec![TextSection {
-                        value: "Play".to_string(),
-                        style: TextStyle {
-                            font: font_assets.fira_sans.clone(),
-                            font_size: 40.0,
-                            color: Color::rgb(0.9, 0.9, 0.9),
-                        },
-                    }],
-                    alignment: Default::default(),
-                },
-                ..Default::default()
-            });
+            parent
+                .spawn_bundle(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: font_assets.fira_sans.clone(),
+                        font_size: 30.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                    },
+                ))
+                .insert(LevelText);
+            spawn_button(
+                parent,
+                &font_assets,
+                &button_colors,
+                "Play",
+                MenuButton::Play,
+            );
+            spawn_button(
+                parent,
+                &font_assets,
+                &button_colors,
+                "Level",
+                MenuButton::NextLevel,
+            );
+            spawn_button(
+                parent,
+                &font_assets,
+                &button_colors,
+                "Editor",
+                MenuButton::Editor,
+            );
+            spawn_button(
+                parent,
+                &font_assets,
+                &button_colors,
+                "Settings",
+                MenuButton::Settings,
+            );
         });
 }
 
-fn click_play_button(
+#[allow(clippy::type_complexity)]
+fn click_menu_buttons(
     button_colors: Res<ButtonColors>,
     mut state: ResMut<State<GameState>>,
+    mut levels: ResMut<Levels>,
     mut interaction_query: Query<
-        (&Interaction, &mut UiColor),
+        (&Interaction, &mut UiColor, &MenuButton),
         (Changed<Interaction>, With<Button>),
     >,
 ) {
-    for (interaction, mut color) in &mut interaction_query {
+    for (interaction, mut color, button) in &mut interaction_query {
         match *interaction {
-            Interaction::Clicked => {
-                state.set(GameState::Playing).unwrap();
-            }
+            // The transitions fail when F2 opened the editor during the same frame, which then wins
+            Interaction::Clicked => match button {
+                MenuButton::Play => {
+                    let _ = state.set(GameState::Playing);
+                }
+                MenuButton::NextLevel => levels.select_next(),
+                MenuButton::Editor => {
+                    let _ = state.set(GameState::Editor);
+                }
+                MenuButton::Settings => {
+                    let _ = state.push(GameState::Settings);
+                }
+            },
             Interaction::Hovered => {
                 *color = button_colors.hovered;
             }
@@ -88,6 +195,8 @@ fn click_play_button(
     }
 }
 
-fn cleanup_menu(mut commands: Commands, button: Query<Entity, With<Button>>) {
-    commands.entity(button.single()).despawn_recursive();
+fn update_level_text(mut level_text: Query<&mut Text, With<LevelText>>, levels: Res<Levels>) {
+    for mut text in &mut level_text {
+        text.sections[0].value = format!("Level: {}", levels.current().name);
+    }
 }
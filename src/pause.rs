@@ -0,0 +1,162 @@
+use crate::actions::{GameControl, Keymap};
+use crate::cleanup::DespawnOnExit;
+use crate::loading::FontAssets;
+use crate::menu::{spawn_button, ButtonColors};
+use crate::{GameState, RunTransition};
+use bevy::prelude::*;
+use bevy::window::WindowFocused;
+
+pub struct PausePlugin;
+
+/// This plugin pushes `GameState::Paused` on top of `GameState::Playing` when the pause key
+/// (Esc by default) or a gamepad's Start button is pressed, or when the window loses focus
+/// or a gamepad gets disconnected.
+/// Everything driven by the gameplay tick stops while the run is paused,
+/// see [crate::game::MainGamePlugin].
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::new()
+                .label(RunTransition::Pause)
+                .after(RunTransition::GameOver)
+                .with_system(toggle_pause)
+                .with_system(pause_on_focus_lost)
+                .with_system(pause_on_gamepad_disconnected),
+        )
+        .add_system_set(SystemSet::on_enter(GameState::Paused).with_system(setup_pause))
+        .add_system_set(SystemSet::on_update(GameState::Paused).with_system(click_pause_buttons));
+    }
+}
+
+#[derive(Component, Clone, Copy)]
+enum PauseButton {
+    Resume,
+    Restart,
+    Settings,
+    Quit,
+}
+
+impl PauseButton {
+    fn label(self) -> &'static str {
+        match self {
+            PauseButton::Resume => "Resume",
+            PauseButton::Restart => "Restart",
+            PauseButton::Settings => "Settings",
+            PauseButton::Quit => "Quit",
+        }
+    }
+}
+
+fn toggle_pause(
+    keymap: Res<Keymap>,
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_input: Res<Input<GamepadButton>>,
+    mut state: ResMut<State<GameState>>,
+) {
+    let pressed = GameControl::Pause.just_pressed(&keymap, &keyboard_input)
+        || gamepads.iter().any(|gamepad| {
+            gamepad_input.just_pressed(GamepadButton::new(*gamepad, GamepadButtonType::Start))
+        });
+    if !pressed {
+        return;
+    }
+    // These fail when another transition was queued during the same frame, which then wins
+    let _ = match state.current() {
+        GameState::Playing => state.push(GameState::Paused),
+        GameState::Paused => state.pop(),
+        _ => Ok(()),
+    };
+}
+
+fn pause_on_focus_lost(
+    mut focus_events: EventReader<WindowFocused>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if focus_events.iter().any(|event| !event.focused) && state.current() == &GameState::Playing {
+        // Fails when another transition was queued during the same frame, which then wins
+        let _ = state.push(GameState::Paused);
+    }
+}
+
+fn pause_on_gamepad_disconnected(
+    mut gamepad_events: EventReader<GamepadEvent>,
+    mut state: ResMut<State<GameState>>,
+) {
+    let disconnected = gamepad_events
+        .iter()
+        .any(|event| event.event_type == GamepadEventType::Disconnected);
+    if disconnected && state.current() == &GameState::Playing {
+        // Fails when another transition was queued during the same frame, which then wins
+        let _ = state.push(GameState::Paused);
+    }
+}
+
+fn setup_pause(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    button_colors: Res<ButtonColors>,
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+            ..Default::default()
+        })
+        .insert(DespawnOnExit(GameState::Paused))
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle::from_section(
+                "Paused",
+                TextStyle {
+                    font: font_assets.fira_sans.clone(),
+                    font_size: 60.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                },
+            ));
+            for button in [
+                PauseButton::Resume,
+                PauseButton::Restart,
+                PauseButton::Settings,
+                PauseButton::Quit,
+            ] {
+                spawn_button(parent, &font_assets, &button_colors, button.label(), button);
+            }
+        });
+}
+
+#[allow(clippy::type_complexity)]
+fn click_pause_buttons(
+    button_colors: Res<ButtonColors>,
+    mut state: ResMut<State<GameState>>,
+    mut interaction_query: Query<
+        (&Interaction, &mut UiColor, &PauseButton),
+        (Changed<Interaction>, With<Button>),
+    >,
+) {
+    for (interaction, mut color, button) in &mut interaction_query {
+        match *interaction {
+            Interaction::Clicked => {
+                // Fails when the pause key was pressed during the same frame, which then wins
+                let _ = match button {
+                    PauseButton::Resume => state.pop(),
+                    // Replacing the whole stack also exits the paused `Playing` below
+                    PauseButton::Restart => state.replace(GameState::Playing),
+                    PauseButton::Settings => state.push(GameState::Settings),
+                    PauseButton::Quit => state.replace(GameState::Menu),
+                };
+            }
+            Interaction::Hovered => {
+                *color = button_colors.hovered;
+            }
+            Interaction::None => {
+                *color = button_colors.normal;
+            }
+        }
+    }
+}
@@ -0,0 +1,323 @@
+use crate::actions::{GameControl, Keymap};
+use crate::audio::Volume;
+use crate::cleanup::{DespawnOnExit, ResetOnEnterAppExt};
+use crate::display::DisplaySettings;
+use crate::loading::FontAssets;
+use crate::menu::{spawn_button, spawn_sized_button, ButtonColors};
+use crate::GameState;
+use bevy::prelude::*;
+
+pub struct SettingsPlugin;
+
+/// This plugin draws the settings screen and owns the saved [Keymap].
+/// `GameState::Settings` is pushed on top of the screen it was opened from,
+/// and popped by its Back button.
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Keymap::load())
+            .reset_on_enter::<Rebinding>(GameState::Settings)
+            .add_system_set(SystemSet::on_enter(GameState::Settings).with_system(setup_settings))
+            .add_system_set(
+                SystemSet::on_update(GameState::Settings)
+                    .with_system(click_settings_buttons)
+                    .with_system(capture_rebinding)
+                    .with_system(update_volume_text)
+                    .with_system(update_display_text)
+                    .with_system(update_keys_text),
+            );
+    }
+}
+
+const VOLUME_STEP: f32 = 0.1;
+
+#[derive(Component, Clone, Copy)]
+enum SettingsButton {
+    VolumeDown,
+    VolumeUp,
+    NextScaling,
+    ToggleFullscreen,
+    /// The next key pressed replaces the keys of the control
+    SetKeys(GameControl),
+    /// The next key pressed is bound to the control, on top of its current keys
+    AddKey(GameControl),
+    ResetKeys,
+    Back,
+}
+
+#[derive(Component)]
+struct VolumeText;
+
+#[derive(Component)]
+struct DisplayText;
+
+#[derive(Component)]
+struct KeysText(GameControl);
+
+/// The control waiting for a key, if any, and whether that key replaces its current ones
+#[derive(Default)]
+struct Rebinding(Option<(GameControl, bool)>);
+
+fn setup_settings(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    button_colors: Res<ButtonColors>,
+) {
+    let text_style = |font_size| TextStyle {
+        font: font_assets.fira_sans.clone(),
+        font_size,
+        color: Color::rgb(0.9, 0.9, 0.9),
+    };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::rgb(0.1, 0.1, 0.1).into(),
+            ..Default::default()
+        })
+        .insert(DespawnOnExit(GameState::Settings))
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle::from_section("Settings", text_style(60.0)));
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    color: Color::NONE.into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    spawn_sized_button(
+                        parent,
+                        &font_assets,
+                        &button_colors,
+                        "-",
+                        Size::new(Val::Px(50.0), Val::Px(40.0)),
+                        30.0,
+                        SettingsButton::VolumeDown,
+                    );
+                    parent
+                        .spawn_bundle(TextBundle::from_sections([
+                            TextSection::new("Volume: ", text_style(30.0)),
+                            TextSection::from_style(text_style(30.0)),
+                        ]))
+                        .insert(VolumeText);
+                    spawn_sized_button(
+                        parent,
+                        &font_assets,
+                        &button_colors,
+                        "+",
+                        Size::new(Val::Px(50.0), Val::Px(40.0)),
+                        30.0,
+                        SettingsButton::VolumeUp,
+                    );
+                });
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    color: Color::NONE.into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(TextBundle::from_section("", text_style(24.0)))
+                        .insert(DisplayText);
+                    for (label, button) in [
+                        ("Scaling", SettingsButton::NextScaling),
+                        ("Fullscreen", SettingsButton::ToggleFullscreen),
+                    ] {
+                        spawn_sized_button(
+                            parent,
+                            &font_assets,
+                            &button_colors,
+                            label,
+                            Size::new(Val::Px(130.0), Val::Px(32.0)),
+                            24.0,
+                            button,
+                        );
+                    }
+                });
+            for control in GameControl::ALL {
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        color: Color::NONE.into(),
+                        ..Default::default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn_bundle(
+                            TextBundle::from_section(control.label(), text_style(24.0)).with_style(
+                                Style {
+                                    size: Size::new(Val::Px(100.0), Val::Auto),
+                                    ..Default::default()
+                                },
+                            ),
+                        );
+                        parent
+                            .spawn_bundle(
+                                TextBundle::from_section("", text_style(24.0)).with_style(Style {
+                                    size: Size::new(Val::Px(300.0), Val::Auto),
+                                    ..Default::default()
+                                }),
+                            )
+                            .insert(KeysText(control));
+                        for (label, button) in [
+                            ("Set", SettingsButton::SetKeys(control)),
+                            ("Add", SettingsButton::AddKey(control)),
+                        ] {
+                            spawn_sized_button(
+                                parent,
+                                &font_assets,
+                                &button_colors,
+                                label,
+                                Size::new(Val::Px(70.0), Val::Px(32.0)),
+                                24.0,
+                                button,
+                            );
+                        }
+                    });
+            }
+            parent
+                .spawn_bundle(NodeBundle {
+                    color: Color::NONE.into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    spawn_button(
+                        parent,
+                        &font_assets,
+                        &button_colors,
+                        "Reset keys",
+                        SettingsButton::ResetKeys,
+                    );
+                    spawn_button(
+                        parent,
+                        &font_assets,
+                        &button_colors,
+                        "Back",
+                        SettingsButton::Back,
+                    );
+                });
+        });
+}
+
+#[allow(clippy::type_complexity)]
+fn click_settings_buttons(
+    button_colors: Res<ButtonColors>,
+    mut state: ResMut<State<GameState>>,
+    mut volume: ResMut<Volume>,
+    mut display: ResMut<DisplaySettings>,
+    mut keymap: ResMut<Keymap>,
+    mut rebinding: ResMut<Rebinding>,
+    mut interaction_query: Query<
+        (&Interaction, &mut UiColor, &SettingsButton),
+        (Changed<Interaction>, With<Button>),
+    >,
+) {
+    for (interaction, mut color, button) in &mut interaction_query {
+        match *interaction {
+            Interaction::Clicked => match button {
+                SettingsButton::VolumeDown => volume.0 = (volume.0 - VOLUME_STEP).max(0.),
+                SettingsButton::VolumeUp => volume.0 = (volume.0 + VOLUME_STEP).min(1.),
+                SettingsButton::NextScaling => {
+                    display.scaling = display.scaling.next();
+                    display.save();
+                }
+                SettingsButton::ToggleFullscreen => {
+                    display.fullscreen = !display.fullscreen;
+                    display.save();
+                }
+                SettingsButton::SetKeys(control) => rebinding.0 = Some((*control, true)),
+                SettingsButton::AddKey(control) => rebinding.0 = Some((*control, false)),
+                SettingsButton::ResetKeys => {
+                    *keymap = Keymap::default();
+                    keymap.save();
+                }
+                SettingsButton::Back => state.pop().unwrap(),
+            },
+            Interaction::Hovered => {
+                *color = button_colors.hovered;
+            }
+            Interaction::None => {
+                *color = button_colors.normal;
+            }
+        }
+    }
+}
+
+fn update_volume_text(mut query: Query<&mut Text, With<VolumeText>>, volume: Res<Volume>) {
+    for mut text in &mut query {
+        text.sections[1].value = format!("{:.0}%", volume.0 * 100.);
+    }
+}
+
+fn update_display_text(
+    mut query: Query<&mut Text, With<DisplayText>>,
+    display: Res<DisplaySettings>,
+) {
+    for mut text in &mut query {
+        text.sections[0].value = format!(
+            "Display: {}, {}",
+            display.scaling.label(),
+            if display.fullscreen {
+                "fullscreen"
+            } else {
+                "windowed"
+            }
+        );
+    }
+}
+
+fn capture_rebinding(
+    mut rebinding: ResMut<Rebinding>,
+    mut keymap: ResMut<Keymap>,
+    keyboard_input: Res<Input<KeyCode>>,
+) {
+    let (control, replace) = match rebinding.0 {
+        Some(pending) => pending,
+        None => return,
+    };
+    if let Some(key) = keyboard_input.get_just_pressed().next() {
+        if replace {
+            keymap.set(control, *key);
+        } else {
+            keymap.add(control, *key);
+        }
+        keymap.save();
+        rebinding.0 = None;
+    }
+}
+
+fn update_keys_text(
+    mut query: Query<(&mut Text, &KeysText)>,
+    keymap: Res<Keymap>,
+    rebinding: Res<Rebinding>,
+) {
+    if !keymap.is_changed() && !rebinding.is_changed() {
+        return;
+    }
+    for (mut text, keys_text) in &mut query {
+        text.sections[0].value = match rebinding.0 {
+            Some((control, _)) if control == keys_text.0 => "press a key...".to_string(),
+            _ => {
+                let keys: Vec<_> = keymap
+                    .keys(keys_text.0)
+                    .iter()
+                    .map(|key| format!("{key:?}"))
+                    .collect();
+                keys.join(", ")
+            }
+        };
+    }
+}
@@ -0,0 +1,175 @@
+use crate::cleanup::DespawnOnExit;
+use crate::game::{Balance, BuyUpgrade, EnergyPoint, Upgrades, Wave, WaveCleared};
+use crate::loading::FontAssets;
+use crate::menu::{spawn_button, spawn_sized_button, ButtonColors};
+use crate::{GameState, RunTransition};
+use bevy::prelude::*;
+
+pub struct ShopPlugin;
+
+/// This plugin pushes `GameState::Shop` on top of `GameState::Playing` once a wave is cleared.
+/// The run stays frozen while energy is spent on core upgrades, and the rest before the next
+/// wave only starts counting down when the shop is closed.
+impl Plugin for ShopPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing).with_system(
+                open_shop
+                    .label(RunTransition::Shop)
+                    .after(RunTransition::GameOver)
+                    .after(RunTransition::Pause),
+            ),
+        )
+        .add_system_set(SystemSet::on_enter(GameState::Shop).with_system(setup_shop))
+        .add_system_set(
+            SystemSet::on_update(GameState::Shop)
+                .with_system(click_shop_buttons)
+                .with_system(update_shop_texts),
+        );
+    }
+}
+
+#[derive(Component, Clone, Copy)]
+enum ShopButton {
+    /// Buys the next level of the upgrade at this index of [Balance::upgrades]
+    Buy(usize),
+    Continue,
+}
+
+#[derive(Component)]
+struct ShopEnergyText;
+
+#[derive(Component)]
+struct UpgradeText(usize);
+
+fn open_shop(mut wave_cleared: EventReader<WaveCleared>, mut state: ResMut<State<GameState>>) {
+    if wave_cleared.iter().last().is_some() {
+        // Fails when the run ended or was paused during the same frame, the shop is skipped then
+        let _ = state.push(GameState::Shop);
+    }
+}
+
+fn setup_shop(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    button_colors: Res<ButtonColors>,
+    balance: Res<Balance>,
+    wave: Res<Wave>,
+) {
+    let text_style = |font_size| TextStyle {
+        font: font_assets.fira_sans.clone(),
+        font_size,
+        color: Color::rgb(0.9, 0.9, 0.9),
+    };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+            ..Default::default()
+        })
+        .insert(DespawnOnExit(GameState::Shop))
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle::from_section(
+                format!("Wave {} cleared", wave.number),
+                text_style(60.0),
+            ));
+            parent
+                .spawn_bundle(TextBundle::from_section("", text_style(30.0)))
+                .insert(ShopEnergyText);
+            for (index, upgrade) in balance.upgrades.iter().enumerate() {
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        color: Color::NONE.into(),
+                        ..Default::default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn_bundle(
+                            TextBundle::from_section(upgrade.kind.label(), text_style(24.0))
+                                .with_style(Style {
+                                    size: Size::new(Val::Px(180.0), Val::Auto),
+                                    ..Default::default()
+                                }),
+                        );
+                        parent
+                            .spawn_bundle(
+                                TextBundle::from_section("", text_style(24.0)).with_style(Style {
+                                    size: Size::new(Val::Px(200.0), Val::Auto),
+                                    ..Default::default()
+                                }),
+                            )
+                            .insert(UpgradeText(index));
+                        spawn_sized_button(
+                            parent,
+                            &font_assets,
+                            &button_colors,
+                            "Buy",
+                            Size::new(Val::Px(70.0), Val::Px(32.0)),
+                            24.0,
+                            ShopButton::Buy(index),
+                        );
+                    });
+            }
+            spawn_button(
+                parent,
+                &font_assets,
+                &button_colors,
+                "Continue",
+                ShopButton::Continue,
+            );
+        });
+}
+
+#[allow(clippy::type_complexity)]
+fn click_shop_buttons(
+    button_colors: Res<ButtonColors>,
+    mut state: ResMut<State<GameState>>,
+    mut buy_events: EventWriter<BuyUpgrade>,
+    mut interaction_query: Query<
+        (&Interaction, &mut UiColor, &ShopButton),
+        (Changed<Interaction>, With<Button>),
+    >,
+) {
+    for (interaction, mut color, button) in &mut interaction_query {
+        match *interaction {
+            Interaction::Clicked => match button {
+                ShopButton::Buy(index) => buy_events.send(BuyUpgrade(*index)),
+                ShopButton::Continue => state.pop().unwrap(),
+            },
+            Interaction::Hovered => {
+                *color = button_colors.hovered;
+            }
+            Interaction::None => {
+                *color = button_colors.normal;
+            }
+        }
+    }
+}
+
+fn update_shop_texts(
+    mut energy_text: Query<&mut Text, (With<ShopEnergyText>, Without<UpgradeText>)>,
+    mut upgrade_texts: Query<(&mut Text, &UpgradeText)>,
+    balance: Res<Balance>,
+    upgrades: Res<Upgrades>,
+    energy: Res<EnergyPoint>,
+) {
+    for mut text in &mut energy_text {
+        text.sections[0].value = format!("Energy: {}", energy.0);
+    }
+    for (mut text, upgrade_text) in &mut upgrade_texts {
+        let level = upgrades.levels[upgrade_text.0];
+        text.sections[0].value = match upgrades.next_cost(&balance, upgrade_text.0) {
+            Some(cost) => format!("Lv {level}, next {cost}"),
+            None => format!("Lv {level}, max"),
+        };
+    }
+}
@@ -0,0 +1,51 @@
+//! Small text blobs kept between two launches of the game, like the keymap.
+//! They are files in the user's config directory on desktop and `localStorage` entries on the web.
+
+#[cfg(not(target_arch = "wasm32"))]
+mod backend {
+    use std::path::PathBuf;
+
+    fn path(key: &str) -> Option<PathBuf> {
+        let config_dir = std::env::var_os("APPDATA")
+            .or_else(|| std::env::var_os("XDG_CONFIG_HOME"))
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_dir.join("td-platformer").join(format!("{key}.ron")))
+    }
+
+    pub fn load(key: &str) -> Option<String> {
+        std::fs::read_to_string(path(key)?).ok()
+    }
+
+    pub fn save(key: &str, contents: &str) -> Result<(), String> {
+        let path = path(key).ok_or("no config directory")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+        }
+        std::fs::write(&path, contents).map_err(|err| err.to_string())
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod backend {
+    fn storage() -> Option<web_sys::Storage> {
+        web_sys::window()?.local_storage().ok()?
+    }
+
+    fn item(key: &str) -> String {
+        format!("td-platformer.{key}")
+    }
+
+    pub fn load(key: &str) -> Option<String> {
+        storage()?.get_item(&item(key)).ok()?
+    }
+
+    pub fn save(key: &str, contents: &str) -> Result<(), String> {
+        storage()
+            .ok_or("no localStorage")?
+            .set_item(&item(key), contents)
+            .map_err(|err| format!("{err:?}"))
+    }
+}
+
+pub use backend::{load, save};
@@ -0,0 +1,290 @@
+use crate::actions::{Actions, ActionsLabel};
+use crate::cleanup::DespawnOnExit;
+use crate::loading::FontAssets;
+use crate::{GameState, RunTransition};
+use bevy::input::touch::TouchInput;
+use bevy::prelude::*;
+
+pub struct TouchControlsPlugin;
+
+/// This plugin draws an on-screen joystick with jump, build, attack and pause buttons once a touch
+/// is detected, and turns the touches into [Actions] like the keyboard and gamepads do.
+impl Plugin for TouchControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TouchControls>()
+            .add_system(detect_touch)
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing).with_system(setup_touch_controls),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(show_touch_controls)
+                    .with_system(
+                        set_touch_actions
+                            .after(ActionsLabel)
+                            // Its pause button
+                            .label(RunTransition::Pause)
+                            .after(RunTransition::GameOver),
+                    ),
+            );
+    }
+}
+
+const JOYSTICK_SIZE: f32 = 140.;
+const KNOB_SIZE: f32 = 50.;
+const BUTTON_SIZE: f32 = 90.;
+const MARGIN: f32 = 40.;
+
+#[derive(Default)]
+struct TouchControls {
+    /// Set once the player touched the screen, the controls stay visible from then on
+    enabled: bool,
+    /// The touch steering the joystick
+    joystick_touch: Option<u64>,
+}
+
+#[derive(Component)]
+struct TouchControlsRoot;
+
+#[derive(Component)]
+struct Joystick;
+
+#[derive(Component)]
+struct JoystickKnob;
+
+#[derive(Component, Clone, Copy)]
+enum TouchButton {
+    Jump,
+    Build,
+    Attack,
+    Pause,
+}
+
+fn detect_touch(mut touch_events: EventReader<TouchInput>, mut controls: ResMut<TouchControls>) {
+    if touch_events.iter().next().is_some() && !controls.enabled {
+        controls.enabled = true;
+    }
+}
+
+fn setup_touch_controls(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    controls: Res<TouchControls>,
+) {
+    let pad_color = Color::rgba(1.0, 1.0, 1.0, 0.2);
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            visibility: Visibility {
+                is_visible: controls.enabled,
+            },
+            ..Default::default()
+        })
+        .insert(TouchControlsRoot)
+        .insert(DespawnOnExit(GameState::Playing))
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(JOYSTICK_SIZE), Val::Px(JOYSTICK_SIZE)),
+                        position_type: PositionType::Absolute,
+                        position: UiRect {
+                            left: Val::Px(MARGIN),
+                            bottom: Val::Px(MARGIN),
+                            ..default()
+                        },
+                        ..Default::default()
+                    },
+                    color: pad_color.into(),
+                    ..Default::default()
+                })
+                .insert(Joystick)
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(KNOB_SIZE), Val::Px(KNOB_SIZE)),
+                                position_type: PositionType::Absolute,
+                                position: knob_position(Vec2::ZERO),
+                                ..Default::default()
+                            },
+                            color: Color::rgba(1.0, 1.0, 1.0, 0.5).into(),
+                            ..Default::default()
+                        })
+                        .insert(JoystickKnob);
+                });
+            for (button, label, position) in [
+                (
+                    TouchButton::Jump,
+                    "Jump",
+                    UiRect {
+                        right: Val::Px(MARGIN),
+                        bottom: Val::Px(MARGIN),
+                        ..default()
+                    },
+                ),
+                (
+                    TouchButton::Build,
+                    "Build",
+                    UiRect {
+                        right: Val::Px(MARGIN * 1.5 + BUTTON_SIZE),
+                        bottom: Val::Px(MARGIN),
+                        ..default()
+                    },
+                ),
+                (
+                    TouchButton::Attack,
+                    "Atk",
+                    UiRect {
+                        right: Val::Px(MARGIN),
+                        bottom: Val::Px(MARGIN * 1.5 + BUTTON_SIZE),
+                        ..default()
+                    },
+                ),
+                (
+                    TouchButton::Pause,
+                    "II",
+                    UiRect {
+                        right: Val::Px(MARGIN),
+                        top: Val::Px(MARGIN),
+                        ..default()
+                    },
+                ),
+            ] {
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(BUTTON_SIZE), Val::Px(BUTTON_SIZE)),
+                            position_type: PositionType::Absolute,
+                            position,
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        color: pad_color.into(),
+                        ..Default::default()
+                    })
+                    .insert(button)
+                    .with_children(|parent| {
+                        parent.spawn_bundle(TextBundle::from_section(
+                            label,
+                            TextStyle {
+                                font: font_assets.fira_sans.clone(),
+                                font_size: 30.0,
+                                color: Color::rgb(0.9, 0.9, 0.9),
+                            },
+                        ));
+                    });
+            }
+        });
+}
+
+/// Where the knob sits inside the joystick for a movement of length up to 1
+fn knob_position(movement: Vec2) -> UiRect<Val> {
+    let offset = (JOYSTICK_SIZE - KNOB_SIZE) / 2.;
+    UiRect {
+        left: Val::Px(offset + movement.x * offset),
+        bottom: Val::Px(offset + movement.y * offset),
+        ..default()
+    }
+}
+
+fn show_touch_controls(
+    controls: Res<TouchControls>,
+    mut root: Query<&mut Visibility, With<TouchControlsRoot>>,
+) {
+    if controls.is_changed() {
+        for mut visibility in &mut root {
+            visibility.is_visible = controls.enabled;
+        }
+    }
+}
+
+/// Touch positions with the origin at the bottom left of the window, like the UI.
+/// Bevy only flips them for native mobile targets, web touches start from the top.
+fn touch_position(touch: &bevy::input::touch::Touch, windows: &Windows) -> Vec2 {
+    let position = touch.position();
+    if cfg!(target_arch = "wasm32") {
+        let height = windows.get_primary().map_or(0., |window| window.height());
+        Vec2::new(position.x, height - position.y)
+    } else {
+        position
+    }
+}
+
+fn contains(node: &Node, transform: &GlobalTransform, point: Vec2) -> bool {
+    let center = transform.translation().truncate();
+    (point - center).abs().cmple(node.size / 2.).all()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn set_touch_actions(
+    mut actions: ResMut<Actions>,
+    mut controls: ResMut<TouchControls>,
+    mut state: ResMut<State<GameState>>,
+    touches: Res<Touches>,
+    windows: Res<Windows>,
+    buttons: Query<(&Node, &GlobalTransform, &TouchButton)>,
+    joystick: Query<(&Node, &GlobalTransform), With<Joystick>>,
+    mut knob: Query<&mut Style, With<JoystickKnob>>,
+) {
+    if !controls.enabled {
+        return;
+    }
+    let (joystick_node, joystick_transform) = match joystick.get_single() {
+        Ok(joystick) => joystick,
+        Err(_) => return,
+    };
+    for touch in touches.iter_just_pressed() {
+        let position = touch_position(touch, &windows);
+        let pressed = buttons
+            .iter()
+            .find(|(node, transform, _)| contains(node, transform, position));
+        match pressed {
+            Some((_, _, TouchButton::Jump)) => actions.player_jump = Some(true),
+            Some((_, _, TouchButton::Build)) => actions.build_tower = Some(true),
+            Some((_, _, TouchButton::Attack)) => actions.player_attack = Some(true),
+            Some((_, _, TouchButton::Pause)) => {
+                let _ = state.push(GameState::Paused);
+            }
+            None if contains(joystick_node, joystick_transform, position) => {
+                controls.joystick_touch = Some(touch.id());
+            }
+            None => {}
+        }
+    }
+    let jump_held = touches.iter().any(|touch| {
+        let position = touch_position(touch, &windows);
+        buttons.iter().any(|(node, transform, button)| {
+            matches!(button, TouchButton::Jump) && contains(node, transform, position)
+        })
+    });
+    actions.player_jump_held |= jump_held;
+
+    let joystick_touch = controls
+        .joystick_touch
+        .and_then(|id| touches.get_pressed(id));
+    let movement = match joystick_touch {
+        Some(touch) => {
+            let center = joystick_transform.translation().truncate();
+            ((touch_position(touch, &windows) - center) / (JOYSTICK_SIZE / 2.)).clamp_length_max(1.)
+        }
+        None => {
+            if controls.joystick_touch.is_some() {
+                controls.joystick_touch = None;
+            }
+            Vec2::ZERO
+        }
+    };
+    if actions.player_movement.is_none() && movement != Vec2::ZERO {
+        actions.player_movement = Some(movement);
+    }
+    for mut style in &mut knob {
+        style.position = knob_position(movement);
+    }
+}
@@ -0,0 +1,76 @@
+use bevy::prelude::*;
+use bevy::utils::Duration;
+use td_platformer::{Core, EnergyPoint, GameRng, GameSeed, GameState, HeadlessGame, Wave};
+
+const FRAME: Duration = Duration::from_micros(16_667);
+
+/// What a run looks like from outside, compared between two runs
+fn snapshot(game: &mut HeadlessGame) -> (u32, u32, i32, Vec<Vec3>) {
+    let wave = game.world().resource::<Wave>();
+    let (number, spawned) = (wave.number, wave.spawned);
+    let energy = game.world().resource::<EnergyPoint>().0;
+    let world = game.world_mut();
+    let mut cores = world.query_filtered::<&Transform, With<Core>>();
+    let cores = cores
+        .iter(world)
+        .map(|transform| transform.translation)
+        .collect();
+    (number, spawned, energy, cores)
+}
+
+#[test]
+fn starts_a_run_with_a_core() {
+    let mut game = HeadlessGame::with_seed(42);
+    game.run_frames(60, FRAME);
+
+    assert_eq!(
+        game.world().resource::<State<GameState>>().current(),
+        &GameState::Playing
+    );
+    let world = game.world_mut();
+    let mut cores = world.query::<&Core>();
+    let core = cores.single(world);
+    assert!(core.hp > 0);
+}
+
+#[test]
+fn same_seed_plays_the_same_run() {
+    let mut first = HeadlessGame::with_seed(7);
+    let mut second = HeadlessGame::with_seed(7);
+    // Past the first rest, so enemies of the first wave are out
+    first.run_frames(600, FRAME);
+    second.run_frames(600, FRAME);
+
+    let first = snapshot(&mut first);
+    assert!(first.0 >= 1, "the first wave should have started");
+    assert!(first.1 > 0, "the first wave should have spawned enemies");
+    assert_eq!(first, snapshot(&mut second));
+}
+
+/// Goes back to the menu and starts another run, returns the seeds of both runs
+fn seeds_of_two_runs(game: &mut HeadlessGame) -> (u64, u64) {
+    game.step(FRAME);
+    let first = game.world().resource::<GameSeed>().0;
+    for state in [GameState::Menu, GameState::Playing] {
+        game.world_mut()
+            .resource_mut::<State<GameState>>()
+            .set(state)
+            .unwrap();
+        game.step(FRAME);
+    }
+    let second = game.world().resource::<GameSeed>().0;
+    assert_eq!(game.world().resource::<GameRng>().seed(), second);
+    (first, second)
+}
+
+#[test]
+fn every_run_gets_a_new_seed() {
+    let (first, second) = seeds_of_two_runs(&mut HeadlessGame::new());
+    assert_ne!(first, second);
+}
+
+#[test]
+fn given_seed_is_kept_across_runs() {
+    let (first, second) = seeds_of_two_runs(&mut HeadlessGame::with_seed(7));
+    assert_eq!((first, second), (7, 7));
+}
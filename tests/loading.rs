@@ -0,0 +1,93 @@
+use bevy::asset::{Asset, AssetPlugin, AssetServerSettings, LoadState};
+use bevy::prelude::*;
+use bevy::utils::Duration;
+use std::path::PathBuf;
+use td_platformer::{Balance, BalanceLoader, Level, LevelLoader};
+
+/// An assets folder of its own for each test, removed when dropped
+struct AssetFolder(PathBuf);
+
+impl AssetFolder {
+    fn new(name: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("td-platformer-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    fn write(&self, file: &str, content: &str) {
+        let path = self.0.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    fn app(&self) -> App {
+        let mut app = App::new();
+        app.insert_resource(AssetServerSettings {
+            asset_folder: self.0.display().to_string(),
+            watch_for_changes: false,
+        })
+        .add_plugins(MinimalPlugins)
+        .add_plugin(AssetPlugin);
+        app
+    }
+}
+
+impl Drop for AssetFolder {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Updates the app until the asset is done loading, one way or the other
+fn wait_for<T: Asset>(app: &mut App, handle: &Handle<T>) -> LoadState {
+    for _ in 0..500 {
+        app.update();
+        let state = app.world.resource::<AssetServer>().get_load_state(handle);
+        if !matches!(state, LoadState::NotLoaded | LoadState::Loading) {
+            return state;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    panic!("the asset never finished loading");
+}
+
+#[test]
+fn invalid_balance_file_falls_back_to_the_default() {
+    let folder = AssetFolder::new("balance");
+    folder.write("balance/default.balance.ron", "(core: (hp: -5))");
+    let mut app = folder.app();
+    app.add_asset::<Balance>()
+        .init_asset_loader::<BalanceLoader>();
+
+    let handle: Handle<Balance> = app
+        .world
+        .resource::<AssetServer>()
+        .load("balance/default.balance.ron");
+    assert_eq!(wait_for(&mut app, &handle), LoadState::Loaded);
+    let balances = app.world.resource::<Assets<Balance>>();
+    assert_eq!(
+        balances.get(&handle).unwrap().core.hp,
+        Balance::default().core.hp
+    );
+}
+
+#[test]
+fn invalid_level_file_falls_back_to_the_shipped_one() {
+    let folder = AssetFolder::new("level");
+    folder.write(
+        "levels/plains.level.ron",
+        r#"(name: "Broken", tile_size: 40.0, rows: ["P..", ".."])"#,
+    );
+    let mut app = folder.app();
+    app.add_asset::<Level>().init_asset_loader::<LevelLoader>();
+
+    let handle: Handle<Level> = app
+        .world
+        .resource::<AssetServer>()
+        .load("levels/plains.level.ron");
+    assert_eq!(wait_for(&mut app, &handle), LoadState::Loaded);
+    let levels = app.world.resource::<Assets<Level>>();
+    assert_eq!(levels.get(&handle).unwrap().name, "Plains");
+}
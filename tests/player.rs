@@ -0,0 +1,166 @@
+use bevy::input::keyboard::KeyboardInput;
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+use bevy::utils::Duration;
+use bevy_rapier2d::prelude::Velocity;
+use td_platformer::{Actions, Balance, HeadlessGame, Level, Levels, Player};
+
+const FRAME: Duration = Duration::from_micros(16_667);
+/// Frames the game gets to drop the player onto the ledge and settle
+const SETTLE_FRAMES: usize = 30;
+
+/// The player starts on a ledge four tiles above the floor, with room to fall on its right
+fn ledge_level() -> Level {
+    Level {
+        name: "Ledge".to_string(),
+        tile_size: 40.,
+        rows: [
+            "............",
+            "..........E.",
+            "............",
+            "............",
+            "P...........",
+            "###.........",
+            "............",
+            "............",
+            "..........C.",
+            "############",
+        ]
+        .map(String::from)
+        .to_vec(),
+        path: "levels/ledge.level.ron".to_string(),
+        seed: None,
+    }
+}
+
+fn ledge_game() -> HeadlessGame {
+    let mut game = HeadlessGame::with_seed(1);
+    // No wave gets in the way
+    game.world_mut().resource_mut::<Balance>().first_rest = 1000.;
+    game.world_mut().insert_resource(Levels {
+        levels: vec![ledge_level()],
+        selected: 0,
+    });
+    game.run_frames(SETTLE_FRAMES, FRAME);
+    assert!(grounded(&mut game), "the player should stand on the ledge");
+    game
+}
+
+fn player(game: &mut HeadlessGame) -> (bool, Vec2) {
+    let world = game.world_mut();
+    let mut players = world.query::<(&Player, &Velocity)>();
+    let (player, velocity) = players.single(world);
+    (player.grounded, velocity.linvel)
+}
+
+fn grounded(game: &mut HeadlessGame) -> bool {
+    player(game).0
+}
+
+fn vertical_speed(game: &mut HeadlessGame) -> f32 {
+    player(game).1.y
+}
+
+fn press(game: &mut HeadlessGame, key: KeyCode) {
+    game.world_mut()
+        .resource_mut::<Events<KeyboardInput>>()
+        .send(KeyboardInput {
+            scan_code: 0,
+            key_code: Some(key),
+            state: ButtonState::Pressed,
+        });
+}
+
+fn jump(game: &mut HeadlessGame) {
+    game.world_mut().resource_mut::<Actions>().player_jump = Some(true);
+}
+
+/// Runs right until the player stops standing on the ledge, returns the frames it took
+fn walk_off_ledge(game: &mut HeadlessGame) -> usize {
+    press(game, KeyCode::Right);
+    for frame in 1..=300 {
+        game.step(FRAME);
+        if !grounded(game) {
+            return frame;
+        }
+    }
+    panic!("the player never walked off the ledge");
+}
+
+#[test]
+fn jumps_from_the_ground() {
+    let mut game = ledge_game();
+    jump(&mut game);
+    game.step(FRAME);
+
+    assert!(vertical_speed(&mut game) > 0.);
+}
+
+#[test]
+fn does_not_jump_in_mid_air() {
+    let mut game = ledge_game();
+    jump(&mut game);
+    game.run_frames(10, FRAME);
+    let rising = vertical_speed(&mut game);
+    assert!(rising > 0., "the player should still be rising");
+
+    jump(&mut game);
+    game.step(FRAME);
+    assert!(vertical_speed(&mut game) < rising);
+}
+
+#[test]
+fn jumps_right_after_walking_off_a_ledge() {
+    let mut game = ledge_game();
+    walk_off_ledge(&mut game);
+    // Within the coyote time
+    game.run_frames(3, FRAME);
+    assert!(
+        vertical_speed(&mut game) < 0.,
+        "the player should be falling"
+    );
+
+    jump(&mut game);
+    game.step(FRAME);
+    assert!(vertical_speed(&mut game) > 0.);
+}
+
+#[test]
+fn does_not_jump_long_after_walking_off_a_ledge() {
+    let mut game = ledge_game();
+    walk_off_ledge(&mut game);
+    // Past the coyote time, still far above the floor
+    game.run_frames(12, FRAME);
+    assert!(!grounded(&mut game));
+
+    jump(&mut game);
+    game.step(FRAME);
+    assert!(vertical_speed(&mut game) < 0.);
+}
+
+#[test]
+fn jump_pressed_just_before_landing_triggers_on_touchdown() {
+    // A first run finds out when the player lands, the runs are the same up to the jump press
+    let mut first = ledge_game();
+    let mut landing = walk_off_ledge(&mut first);
+    while !grounded(&mut first) {
+        first.step(FRAME);
+        landing += 1;
+        assert!(landing < 300, "the player never landed");
+    }
+
+    let mut game = ledge_game();
+    let walking = walk_off_ledge(&mut game);
+    // Well within the jump buffer, but long after the coyote time
+    let early = 4;
+    game.run_frames(landing - walking - early, FRAME);
+    jump(&mut game);
+    game.step(FRAME);
+    assert!(
+        vertical_speed(&mut game) < 0.,
+        "the jump should wait for the landing"
+    );
+
+    game.run_frames(early, FRAME);
+    assert!(vertical_speed(&mut game) > 0.);
+}